@@ -1,53 +1,350 @@
-use rand::{distributions::Standard, Rng};
-
+use ethers::middleware::gas_oracle::GasOracle;
 use ethers::prelude::*;
-use ethers_flashbots::BundleRequest;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers_flashbots::{BundleRequest, BundleTransaction};
 
-use eyre::Result;
+use crate::distribution::{ReplayDistribution, SizeDistribution};
+use crate::error::{Result, StressError};
+use crate::payload::PayloadGenerator;
+use crate::set_code::{Authorization, SetCodeTx, PER_AUTHORIZATION_GAS};
+use serde::Serialize;
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 /// 1 kilobyte = 1024 bytes
-const KB: usize = 1024;
+pub const KB: usize = 1024;
 
 /// Arbitrarily chosen number to cover for nonce+from+to+gas price size in a serialized
 /// transaction.  TODO: get the actual overhead from the signing, etc. to pack more fully
-const TRIM_BYTES: usize = 300;
+pub const TRIM_BYTES: usize = 300;
+
+/// block max size is 2MB
+const BLOCK_DATA_BYTES: usize = 2 * 1024 * KB;
+
+/// what `fill_pct` is a percentage of: the calldata a `gas_limit` block can pay for at the
+/// fork's price, up to `BLOCK_DATA_BYTES`, so any fill up to 100% can be built
+pub fn block_data_bytes(gas_limit: U256, fork: Fork) -> usize {
+    let affordable = gas_limit / fork.calldata_gas_per_byte();
+    affordable.min(U256::from(BLOCK_DATA_BYTES)).as_usize()
+}
+
+/// largest `fill_pct` accepted; anything over 100 is a deliberately over-full bundle
+pub const MAX_FILL_PCT: f64 = 200.0;
+
+/// geth won't accept or propagate a transaction larger than this through the mempool
+const MEMPOOL_TX_MAX_BYTES: usize = 128 * KB;
+
+/// network upgrades that change how much calldata fits in a block, detected from the head block
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fork {
+    /// no blob fields in the header yet
+    PreCancun,
+    /// EIP-4844 blob gas fields in the header
+    Cancun,
+    /// `requestsHash` in the header; EIP-7623 puts a floor under the calldata price
+    Prague,
+}
+
+impl Fork {
+    pub async fn detect<M: Middleware>(provider: &M) -> Result<Self>
+    where
+        M::Error: 'static,
+    {
+        // the typed `Block` predates these fields, so look at the raw header
+        let block: serde_json::Value = provider
+            .provider()
+            .request("eth_getBlockByNumber", ("latest", false))
+            .await
+            .map_err(StressError::rpc)?;
+        let fork = if block.get("requestsHash").is_some() {
+            Fork::Prague
+        } else if block.get("excessBlobGas").is_some() {
+            Fork::Cancun
+        } else {
+            Fork::PreCancun
+        };
+        Ok(fork)
+    }
+
+    /// gas per non-zero calldata byte, which is nearly all of a random payload
+    pub fn calldata_gas_per_byte(self) -> u64 {
+        match self {
+            Fork::PreCancun | Fork::Cancun => 16,
+            Fork::Prague => 40,
+        }
+    }
+
+    /// gas per zero calldata byte, a quarter of the non-zero price
+    pub fn zero_byte_gas(self) -> u64 {
+        self.calldata_gas_per_byte() / 4
+    }
+}
+
+/// lower bound on the gas a transaction carrying `data_len` random calldata bytes needs
+pub fn intrinsic_gas(data_len: usize, fork: Fork) -> U256 {
+    U256::from(21_000) + U256::from(data_len) * fork.calldata_gas_per_byte()
+}
+
+/// gas a plain transfer carrying exactly `data` as calldata needs, pricing zero and non-zero
+/// bytes separately.  what we fall back on when the node won't estimate a transaction.
+pub fn calldata_gas(data: &[u8], fork: Fork) -> U256 {
+    let zeros = data.iter().filter(|byte| **byte == 0).count() as u64;
+    let non_zeros = data.len() as u64 - zeros;
+    U256::from(21_000)
+        + U256::from(zeros * fork.zero_byte_gas())
+        + U256::from(non_zeros * fork.calldata_gas_per_byte())
+}
+
+/// reject chunk sizes no transaction can carry: too small to leave room after `TRIM_BYTES`,
+/// or (through the mempool) over geth's transaction size limit.
+pub fn validate_chunk_size(chunk_size: usize, mempool: bool) -> Result<()> {
+    if chunk_size <= TRIM_BYTES {
+        return Err(StressError::InvalidConfig(format!(
+            "chunk size of {chunk_size} bytes leaves no calldata after the {TRIM_BYTES} byte trim"
+        )));
+    }
+    if mempool && chunk_size > MEMPOOL_TX_MAX_BYTES {
+        return Err(StressError::InvalidConfig(format!(
+            "chunk size of {chunk_size} bytes exceeds the {MEMPOOL_TX_MAX_BYTES} byte mempool transaction limit"
+        )));
+    }
+    Ok(())
+}
+
+/// reject a bundle whose calldata can't fit under the block gas limit at the fork's pricing
+fn validate_block_data(total_data_size: usize, gas_limit: U256, fork: Fork) -> Result<()> {
+    let max_bytes = gas_limit / fork.calldata_gas_per_byte();
+    if U256::from(total_data_size) > max_bytes {
+        return Err(StressError::InvalidConfig(format!(
+            "{total_data_size} bytes of calldata exceeds the {fork:?} limit of {max_bytes} bytes \
+             for a {gas_limit} gas block ({} gas per byte); lower --fill-pct",
+            fork.calldata_gas_per_byte()
+        )));
+    }
+    Ok(())
+}
+
+/// the order a bundle's transactions are signed and pushed in.  nonces follow that order, so the
+/// bundle stays valid whichever it is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BundleOrder {
+    /// full chunks first, then the remainder transaction
+    AsConstructed,
+    /// most calldata first
+    LargestFirst,
+    /// least calldata first, so the remainder transaction leads
+    SmallestFirst,
+}
+
+impl BundleOrder {
+    /// put the per-transaction calldata sizes in this order
+    pub fn apply(self, sizes: &mut [usize]) {
+        match self {
+            BundleOrder::AsConstructed => {}
+            BundleOrder::LargestFirst => sizes.sort_by(|a, b| b.cmp(a)),
+            BundleOrder::SmallestFirst => sizes.sort(),
+        }
+    }
+}
+
+/// everything about the transactions we sign that stays fixed for the whole run
+#[derive(Debug, Clone)]
+pub struct TxParams {
+    pub chain_id: u64,
+    pub address: Address,
+    pub receiver: Address,
+    /// applied on top of `estimate_gas` to leave headroom for state changes before inclusion
+    pub gas_multiplier: f64,
+    /// the gas limit of a single transaction never goes above this (the block gas limit)
+    pub gas_cap: U256,
+    pub fork: Fork,
+    /// use this gas limit for every transaction instead of calling `estimate_gas`
+    pub assume_gas: Option<U256>,
+    /// in bundles, never tip less than this percentile of recent priority fees
+    pub priority_fee_percentile: Option<f64>,
+    /// the legacy gas price (base price plus tip), or the type-2 max fee, never goes above this
+    pub gas_price_cap: Option<U256>,
+    /// tip this percentage of the latest base fee instead of a fixed `tip_wei`
+    pub tip_pct_over_base: Option<f64>,
+    /// makes each transaction's calldata
+    pub payload: Arc<dyn PayloadGenerator>,
+    /// in bundles, the order of the transactions by calldata size
+    pub bundle_order: BundleOrder,
+    /// never put more than this many transactions in a bundle, dropping the rest of the fill
+    pub max_txs_per_bundle: Option<usize>,
+    /// in bundles, sample each transaction's chunk size from this instead of using `chunk_size`
+    pub size_distribution: Option<SizeDistribution>,
+    /// in bundles, replay these captured calldata sizes instead of chunking the fill
+    pub replay_distribution: Option<ReplayDistribution>,
+    /// sign bundle transactions as type-2, with the tip as the max priority fee
+    pub eip1559: bool,
+    /// send each transaction a value drawn uniformly from MIN..=MAX wei, instead of nothing
+    pub value_range: Option<(u64, u64)>,
+    /// vary each transaction's gas price, or its tip if it has one, by a random amount within
+    /// ± this percent, never under the base fee
+    pub gas_price_jitter_pct: f64,
+    /// in bundles, put an ordinary transfer from this signer ahead of the calldata transactions
+    pub normal_tx_signer: Option<LocalWallet>,
+    /// in bundles, let the calldata transactions revert without invalidating the bundle
+    pub allow_reverts: bool,
+    /// build every gas price on this instead of the network's current one
+    pub gas_price_snapshot: Option<U256>,
+    /// where the network gas price of legacy transactions comes from
+    pub gas_oracle: Arc<dyn GasOracle>,
+    /// sign the calldata transactions as EIP-7702 set-code transactions instead
+    pub set_code: Option<SetCode>,
+}
+
+/// transaction types besides legacy and `--eip1559`'s type-2, picked with `--tx-type`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum, Serialize)]
+pub enum TxType {
+    /// EIP-7702 set-code transactions carrying `--authorization-list`, from Prague on
+    #[value(name = "7702")]
+    #[serde(rename = "7702")]
+    SetCode,
+}
+
+/// what set-code transactions need that the signing middleware can't give: ethers 1.0 can't
+/// sign them, so the tx signer's key, and the authorizations each one carries
+#[derive(Debug, Clone)]
+pub struct SetCode {
+    pub signer: LocalWallet,
+    pub authorizations: Vec<Authorization>,
+}
+
+/// clamp `gas_price` to `gas_price_cap`, if there is one
+pub fn capped_gas_price(params: &TxParams, gas_price: U256) -> U256 {
+    match params.gas_price_cap {
+        Some(cap) if gas_price > cap => cap,
+        _ => gas_price,
+    }
+}
 
 #[tracing::instrument(skip_all, name = "construct_bundle")]
 fn construct_tx(
-    chain_id: u64,
-    address: Address,
-    receiver: Address,
+    params: &TxParams,
     data_size: usize,
     gas_price: U256,
 ) -> ethers::prelude::TransactionRequest {
     // Craft the transaction.  data_size is in bytes
-    let blob = generate_random_data(data_size);
+    let blob = params.payload.generate(data_size);
 
-    let tx = TransactionRequest::new()
-        .chain_id(chain_id)
-        .value(0)
-        .from(address)
-        .to(receiver)
+    let value = match params.value_range {
+        Some((min, max)) => fastrand::u64(min..=max),
+        None => 0,
+    };
+
+    TransactionRequest::new()
+        .chain_id(params.chain_id)
+        .value(value)
+        .from(params.address)
+        .to(params.receiver)
         .data(blob)
-        .gas_price(gas_price);
+        .gas_price(gas_price)
+}
+
+/// scale the estimate by `gas_multiplier`, without going over `gas_cap`
+fn apply_gas_multiplier(estimate: U256, params: &TxParams) -> U256 {
+    let scaled = U256::from((estimate.as_u128() as f64 * params.gas_multiplier) as u128);
+    scaled.min(params.gas_cap)
+}
+
+/// `assume_gas`, or else the scaled `estimate_gas`.  nodes that refuse to estimate a
+/// transaction this big fall back to `calldata_gas`, with a warning.
+async fn gas_limit<M: Middleware>(
+    params: &TxParams,
+    provider: &M,
+    tx: &TransactionRequest,
+) -> Result<U256>
+where
+    M::Error: 'static,
+{
+    if let Some(gas) = params.assume_gas {
+        return Ok(gas);
+    }
+    Ok(authorization_gas(params) + estimated_gas(params, provider, tx).await?)
+}
+
+/// what a set-code transaction's authorizations add on top of its estimate
+fn authorization_gas(params: &TxParams) -> U256 {
+    match &params.set_code {
+        Some(set_code) => U256::from(PER_AUTHORIZATION_GAS * set_code.authorizations.len() as u64),
+        None => U256::zero(),
+    }
+}
 
-    return tx;
+/// whether `estimated_gas` has warned about falling back to `calldata_gas` yet this run
+static FALLBACK_WARNED: AtomicBool = AtomicBool::new(false);
+
+/// a node refusing to estimate a transaction for its size (geth's "oversized data", a gas
+/// limit or cap it's over), rather than because it would revert or the request failed
+fn is_size_rejection(err: &impl std::fmt::Display) -> bool {
+    let err = err.to_string().to_lowercase();
+    ["oversized", "too large", "limit"]
+        .iter()
+        .any(|reason| err.contains(reason))
+}
+
+/// the scaled `estimate_gas` of `tx`, or of its calldata alone if the node won't estimate it
+/// for its size.  any other error, a revert included, is returned.
+async fn estimated_gas<M: Middleware>(
+    params: &TxParams,
+    provider: &M,
+    tx: &TransactionRequest,
+) -> Result<U256>
+where
+    M::Error: 'static,
+{
+    match provider.estimate_gas(&tx.clone().into(), None).await {
+        Ok(estimate) => Ok(apply_gas_multiplier(estimate, params)),
+        Err(err) if is_size_rejection(&err) => {
+            let data = tx.data.as_deref().unwrap_or_default();
+            let fallback = calldata_gas(data, params.fork);
+            if FALLBACK_WARNED.swap(true, Ordering::Relaxed) {
+                tracing::debug!(
+                    "estimate_gas rejected {} bytes of calldata ({err}), using {fallback} gas",
+                    data.len()
+                );
+            } else {
+                tracing::warn!(
+                    "estimate_gas rejected {} bytes of calldata ({err}), using {fallback} gas from the calldata alone; further fallbacks are only logged at debug",
+                    data.len()
+                );
+            }
+            Ok(apply_gas_multiplier(fallback, params))
+        }
+        Err(err) => Err(StressError::rpc(err)),
+    }
 }
 
+/// sign a transaction with `chunk` bytes of calldata.  with a `max_priority_fee` it's a
+/// type-2 transaction and `gas_price` is its max fee, otherwise a legacy one.  with
+/// `set_code` it's a set-code transaction, tipping `max_priority_fee` or else all of
+/// `gas_price`.
 pub async fn get_signed_tx<M: Middleware>(
-    chain_id: u64,
-    address: H160,
-    receiver: H160,
+    params: &TxParams,
     chunk: usize,
     gas_price: U256,
+    max_priority_fee: Option<U256>,
     provider: M,
     nonce: U256,
 ) -> Result<Bytes>
 where
     M::Error: 'static,
 {
-    let mut tx = construct_tx(chain_id, address, receiver, chunk, gas_price);
-    let gas_per_tx = provider.estimate_gas(&tx.clone().into(), None).await?;
+    let floor = if params.gas_price_jitter_pct > 0.0 {
+        jitter_floor(&provider, max_priority_fee).await?
+    } else {
+        U256::zero()
+    };
+    let (gas_price, max_priority_fee) =
+        jitter_gas_price(gas_price, max_priority_fee, floor, params);
+    let mut tx = construct_tx(params, chunk, capped_gas_price(params, gas_price));
+    // the tip can't be more than the whole (capped) max fee
+    let max_priority_fee = max_priority_fee.map(|tip| tip.min(tx.gas_price.unwrap_or(tip)));
+    let gas_per_tx = gas_limit(params, &provider, &tx).await?;
     // tracing::debug!("tx cost {} gas", gas_per_tx);
     // let blob_len = tx.data.as_ref().map(|x| x.len()).unwrap_or_default();
 
@@ -55,10 +352,28 @@ where
     tx.nonce = Some(nonce);
     tx.gas = Some(gas_per_tx);
 
-    // make into typed tx for the signer
-    let tx = tx.into();
+    if let Some(set_code) = &params.set_code {
+        let max_fee = tx.gas_price.unwrap_or_default();
+        let tx = SetCodeTx {
+            chain_id: params.chain_id,
+            nonce,
+            max_priority_fee_per_gas: max_priority_fee.unwrap_or(max_fee),
+            max_fee_per_gas: max_fee,
+            gas: gas_per_tx,
+            to: params.receiver,
+            value: tx.value.unwrap_or_default(),
+            data: tx.data.unwrap_or_default(),
+            authorizations: set_code.authorizations.clone(),
+        };
+        return Ok(tx.sign(&set_code.signer));
+    }
+
+    let tx = typed_tx(tx, max_priority_fee);
     let sender = provider.default_sender().unwrap_or_default();
-    let signature = provider.sign_transaction(&tx, sender).await?;
+    let signature = provider
+        .sign_transaction(&tx, sender)
+        .await
+        .map_err(StressError::rpc)?;
     let rlp = tx.rlp_signed(&signature);
 
     // println!("{}", serde_json::to_string(&tx)?);
@@ -69,28 +384,335 @@ where
     Ok(rlp)
 }
 
-fn generate_random_data(size: usize) -> Vec<u8> {
-    // size is bytes
-    let blob = rand::thread_rng()
-        .sample_iter(Standard)
-        .take(size)
-        .collect::<Vec<u8>>();
-    return blob;
+/// `gas_price` and `max_priority_fee` with a random factor within ± `gas_price_jitter_pct`
+/// applied.  with a `max_priority_fee` only the tip is scaled, the max fee moving with it,
+/// so the base fee part stays whole.  otherwise the whole gas price is scaled, but never
+/// below `floor`, the next block's base fee, or the transaction couldn't be included (and
+/// would take its bundle down with it).
+fn jitter_gas_price(
+    gas_price: U256,
+    max_priority_fee: Option<U256>,
+    floor: U256,
+    params: &TxParams,
+) -> (U256, Option<U256>) {
+    if params.gas_price_jitter_pct <= 0.0 {
+        return (gas_price, max_priority_fee);
+    }
+    let factor = 1.0 + (fastrand::f64() * 2.0 - 1.0) * params.gas_price_jitter_pct / 100.0;
+    let bps = U256::from((factor * 10_000.0).round() as u64);
+    let scale = |price: U256| price * bps / 10_000;
+    match max_priority_fee {
+        Some(tip) => {
+            let jittered = scale(tip);
+            (gas_price.saturating_sub(tip) + jittered, Some(jittered))
+        }
+        None => (scale(gas_price).max(floor), None),
+    }
+}
+
+/// the least a legacy transaction with jittered gas price may pay: the next block's base fee,
+/// or nothing on chains without one
+async fn jitter_floor<M: Middleware>(provider: &M, max_priority_fee: Option<U256>) -> Result<U256>
+where
+    M::Error: 'static,
+{
+    if max_priority_fee.is_some() {
+        // only the tip is jittered, which can't take the max fee under the base fee
+        return Ok(U256::zero());
+    }
+    Ok(provider
+        .get_block(BlockNumber::Latest)
+        .await
+        .map_err(StressError::rpc)?
+        .and_then(|block| block.next_block_base_fee())
+        .unwrap_or_default())
+}
+
+/// the lowest and highest price per gas `txs` offer the block builder: the tip of type-2
+/// transactions, otherwise the gas price.  for logging what `gas_price_jitter_pct` produced.
+pub fn gas_price_range<'a>(txs: impl IntoIterator<Item = &'a Transaction>) -> Option<(U256, U256)> {
+    txs.into_iter()
+        .map(|tx| {
+            tx.max_priority_fee_per_gas
+                .or(tx.gas_price)
+                .unwrap_or_default()
+        })
+        .fold(None, |range, price| match range {
+            None => Some((price, price)),
+            Some((low, high)) => Some((low.min(price), high.max(price))),
+        })
+}
+
+/// make into typed tx for the signer: type-2 with a `max_priority_fee`, the gas price being
+/// its max fee, otherwise legacy
+fn typed_tx(tx: TransactionRequest, max_priority_fee: Option<U256>) -> TypedTransaction {
+    match max_priority_fee {
+        Some(max_priority_fee) => Eip1559TransactionRequest {
+            from: tx.from,
+            to: tx.to,
+            gas: tx.gas,
+            value: tx.value,
+            data: tx.data,
+            nonce: tx.nonce,
+            access_list: Default::default(),
+            max_priority_fee_per_gas: Some(max_priority_fee),
+            max_fee_per_gas: tx.gas_price,
+            chain_id: tx.chain_id,
+        }
+        .into(),
+        None => tx.into(),
+    }
 }
 
-pub fn calldata_kb_to_bytes(chunk_size: usize) -> usize {
-    let calldate_bytes = chunk_size * KB - TRIM_BYTES;
-    calldate_bytes
+/// gas of a plain value transfer
+const TRANSFER_GAS: u64 = 21_000;
+
+/// what the `normal_tx_signer`'s transfer sends the receiver, in wei
+pub const NORMAL_TX_VALUE_WEI: u64 = 1_000_000_000;
+
+/// sign the `normal_tx_signer`'s transfer at `nonce`, priced like the calldata transactions
+async fn sign_normal_tx(
+    params: &TxParams,
+    signer: &LocalWallet,
+    nonce: U256,
+    gas_price: U256,
+    max_priority_fee: Option<U256>,
+) -> Result<Bytes> {
+    let tx = TransactionRequest::new()
+        .chain_id(params.chain_id)
+        .from(signer.address())
+        .to(params.receiver)
+        .value(NORMAL_TX_VALUE_WEI)
+        .gas(TRANSFER_GAS)
+        .gas_price(gas_price)
+        .nonce(nonce);
+    let tx = typed_tx(tx, max_priority_fee);
+    let signature = signer
+        .sign_transaction(&tx)
+        .await
+        .map_err(StressError::rpc)?;
+    Ok(tx.rlp_signed(&signature))
+}
+
+/// calldata carried by a transaction of `chunk_size` bytes, after `TRIM_BYTES` for the
+/// other fields
+pub fn chunk_calldata_bytes(chunk_size: usize) -> usize {
+    chunk_size - TRIM_BYTES
+}
+
+/// calldata size of each transaction needed to carry `total_data_size` bytes: as many full
+/// `chunk`s as fit, then one "remainder" transaction with whatever is left over.  errors if
+/// that leaves no calldata at all rather than handing the relay an empty bundle.
+pub fn tx_data_sizes(total_data_size: usize, chunk: usize) -> Result<Vec<usize>> {
+    let txs_per_block = total_data_size / chunk;
+    let mut sizes = vec![chunk; txs_per_block];
+
+    // fill the "remainder" of the block with leftover datasize
+    let current_data_used = TRIM_BYTES + txs_per_block * chunk;
+    let remaining_data = total_data_size.saturating_sub(current_data_used + TRIM_BYTES);
+    if remaining_data > 0 {
+        sizes.push(remaining_data);
+    }
+
+    if !sizes.iter().any(|size| *size > 0) {
+        return Err(StressError::InvalidConfig(format!(
+            "a {total_data_size} byte fill with {chunk} byte chunks leaves no calldata to send, raise --fill-pct"
+        )));
+    }
+    Ok(sizes)
 }
 
+/// `pct` percent of the latest block's base fee
+async fn tip_over_base_fee<M: Middleware>(provider: &M, pct: f64) -> Result<U256>
+where
+    M::Error: 'static,
+{
+    let base_fee = provider
+        .get_block(BlockNumber::Latest)
+        .await
+        .map_err(StressError::rpc)?
+        .and_then(|block| block.base_fee_per_gas)
+        .ok_or_else(|| {
+            StressError::InvalidConfig(
+                "--tip-pct-over-base needs a base fee, but the latest block has none".to_string(),
+            )
+        })?;
+    // in basis points, like `fill_pct`
+    let tip = base_fee * U256::from((pct * 100.0).round() as u64) / 10_000;
+    tracing::debug!("tip of {pct}% over the {base_fee} base fee: {tip}");
+    Ok(tip)
+}
+
+/// what a transaction's gas price is built on, before the tip: the next block's base fee for
+/// type-2 transactions, otherwise the `gas_oracle`'s price
+pub async fn network_gas_price<M: Middleware>(params: &TxParams, provider: &M) -> Result<U256>
+where
+    M::Error: 'static,
+{
+    if params.eip1559 {
+        next_base_fee(provider).await
+    } else {
+        params.gas_oracle.fetch().await.map_err(StressError::rpc)
+    }
+}
+
+/// base fee of the block after the latest one, which the bundle targets
+async fn next_base_fee<M: Middleware>(provider: &M) -> Result<U256>
+where
+    M::Error: 'static,
+{
+    provider
+        .get_block(BlockNumber::Latest)
+        .await
+        .map_err(StressError::rpc)?
+        .and_then(|block| block.next_block_base_fee())
+        .ok_or_else(|| {
+            StressError::InvalidConfig(
+                "--eip1559 needs a base fee, but the latest block has none".to_string(),
+            )
+        })
+}
+
+/// how many recent blocks the priority fee floor is taken over
+const FEE_HISTORY_BLOCKS: u64 = 5;
+
+/// median over the last few blocks of each block's `percentile` priority fee
+async fn priority_fee_floor<M: Middleware>(provider: &M, percentile: f64) -> Result<U256>
+where
+    M::Error: 'static,
+{
+    let history = provider
+        .fee_history(FEE_HISTORY_BLOCKS, BlockNumber::Latest, &[percentile])
+        .await
+        .map_err(StressError::rpc)?;
+    let mut rewards: Vec<U256> = history
+        .reward
+        .iter()
+        .filter_map(|block| block.first().copied())
+        .collect();
+    rewards.sort();
+    Ok(rewards.get(rewards.len() / 2).copied().unwrap_or_default())
+}
+
+/// chunk sizes `--auto-chunk` picks from
+const AUTO_CHUNK_SIZES: [usize; 8] = [
+    16 * KB,
+    32 * KB,
+    64 * KB,
+    128 * KB,
+    256 * KB,
+    512 * KB,
+    1024 * KB,
+    2048 * KB,
+];
+
+/// gas limit `get_signed_tx` would give a transaction with `data_size` bytes of calldata,
+/// or `None` if the node won't estimate it (a chunk over the block gas limit, say) rather
+/// than the calldata-only fallback, so `tune_chunk_size` can pass over that chunk size
+async fn tx_gas<M: Middleware>(
+    params: &TxParams,
+    provider: &M,
+    data_size: usize,
+    gas_price: U256,
+) -> Option<U256> {
+    if let Some(gas) = params.assume_gas {
+        return Some(gas);
+    }
+    let tx = construct_tx(params, data_size, gas_price);
+    match provider.estimate_gas(&tx.into(), None).await {
+        Ok(estimate) => Some(authorization_gas(params) + apply_gas_multiplier(estimate, params)),
+        Err(err) => {
+            tracing::debug!("--auto-chunk: estimate_gas failed for {data_size} bytes ({err})");
+            None
+        }
+    }
+}
+
+/// pick the chunk size whose bundle carries the most calldata at `fill_pct` while its
+/// summed gas stays under `gas_limit`, leaving out chunks bigger than `max_bundle_bytes`
+/// since a transaction can't be split across sub-bundles.  returns the chunk size and the
+/// fill percentage that bundle actually reaches, which is lower than `fill_pct` when the
+/// transactions had to be cut off at the gas limit.  ties go to the bigger chunk.
+pub async fn tune_chunk_size<M: Middleware>(
+    params: &TxParams,
+    provider: &M,
+    gas_limit: U256,
+    fill_pct: f64,
+    max_bundle_bytes: Option<usize>,
+) -> Result<(usize, f64)>
+where
+    M::Error: 'static,
+{
+    let fill_bps = (fill_pct * 100.0).round() as usize;
+    let block_bytes = block_data_bytes(gas_limit, params.fork);
+    let total_data_size = fill_bps * block_bytes / 10_000;
+    let gas_price = network_gas_price(params, provider).await?;
+
+    let mut best: Option<(usize, usize, f64)> = None;
+    for chunk_size in AUTO_CHUNK_SIZES {
+        if max_bundle_bytes.is_some_and(|max| chunk_size > max) {
+            continue;
+        }
+        let chunk = chunk_calldata_bytes(chunk_size);
+        let Ok(sizes) = tx_data_sizes(total_data_size, chunk) else {
+            continue;
+        };
+        let Some(full_gas) = tx_gas(params, provider, chunk, gas_price).await else {
+            tracing::debug!("--auto-chunk: skipping {chunk_size} byte chunks, they don't estimate");
+            continue;
+        };
+        let (mut gas, mut bytes, mut txs) = (U256::zero(), 0, 0);
+        for size in &sizes {
+            let size_gas = if *size == chunk {
+                full_gas
+            } else {
+                // the trimmed last transaction, cut off here if it won't estimate either
+                match tx_gas(params, provider, *size, gas_price).await {
+                    Some(gas) => gas,
+                    None => break,
+                }
+            };
+            if gas + size_gas > gas_limit || params.max_txs_per_bundle == Some(txs) {
+                break;
+            }
+            gas += size_gas;
+            bytes += size;
+            txs += 1;
+        }
+        let fill = if txs == sizes.len() {
+            fill_pct
+        } else {
+            // smallest fill (in basis points, rounding up) `tx_data_sizes` turns back into
+            // just the transactions that fit
+            let fitted = TRIM_BYTES + txs * chunk;
+            (fitted * 10_000).div_ceil(block_bytes) as f64 / 100.0
+        };
+        tracing::debug!(
+            "--auto-chunk: {chunk_size} byte chunks carry {bytes} bytes in {txs}/{} transactions for {gas} gas",
+            sizes.len()
+        );
+        if bytes > 0 && best.is_none_or(|(_, best_bytes, _)| bytes >= best_bytes) {
+            best = Some((chunk_size, bytes, fill));
+        }
+    }
+    match best {
+        Some((chunk_size, _, fill)) => Ok((chunk_size, fill)),
+        None => Err(StressError::InvalidConfig(format!(
+            "--auto-chunk found no chunk size that fits any calldata at --fill-pct {fill_pct}, or the node wouldn't estimate any of them"
+        ))),
+    }
+}
+
+/// sign a bundle filling `fill_pct` of a `gas_limit` block, its calldata transactions from
+/// `nonce` on.  with a `normal_tx_signer` and a `normal_nonce`, its transfer goes first.
+#[allow(clippy::too_many_arguments)]
 pub async fn construct_bundle<M: Middleware>(
-    chain_id: u64,
-    address: H160,
-    receiver: Address,
+    params: &TxParams,
     provider: M,
     gas_limit: U256,
-    fill_pct: u8,
+    fill_pct: f64,
     mut nonce: U256,
+    normal_nonce: Option<U256>,
     chunk_size: usize,
     tip_wei: u64,
 ) -> Result<BundleRequest>
@@ -99,60 +721,152 @@ where
 {
     // `CHUNKS_SIZE` Kilobytes per transaction, shave off 300 bytes to leave room for
     // the other fields to be serialized.
-    let chunk = calldata_kb_to_bytes(chunk_size);
+    validate_chunk_size(chunk_size, false)?;
+    let chunk = chunk_calldata_bytes(chunk_size);
 
-    // For each block, we want `fill_pct` * 2MB of call data.
-    // we generate FLOOT(2MB / chunk_size) transactions of size "chunk_size"
+    // For each block, we want `fill_pct` * the block's calldata capacity (at most 2MB).
+    // we generate FLOOR(that / chunk_size) transactions of size "chunk_size"
     // and then one final "remainder" transaction to reach the desired fill_pct
-    let gas_used_per_block = gas_limit * fill_pct / 100;
-    let total_data_size: usize = fill_pct as usize * 2 * 1024 * KB / 100; // block max size is 2MB
+    // in basis points, so fractional percentages don't get truncated away
+    let fill_bps = (fill_pct * 100.0).round() as usize;
+    let gas_used_per_block = gas_limit * fill_bps / 10_000;
+    let block_bytes = block_data_bytes(gas_limit, params.fork);
+    let total_data_size: usize = fill_bps * block_bytes / 10_000;
     tracing::debug!(
         "total data size: {}, gas_used_per_block: {}, blob size (bytes) per tx: {}",
         total_data_size,
         gas_used_per_block,
         chunk
     );
+    if fill_pct > 100.0 {
+        tracing::warn!(
+            "--fill-pct {fill_pct} is over-full on purpose, expect the bundle to be rejected"
+        );
+    } else {
+        validate_block_data(total_data_size, gas_limit, params.fork)?;
+    }
+    if let Some(assumed) = params.assume_gas {
+        let needed = intrinsic_gas(chunk, params.fork);
+        if assumed < needed {
+            tracing::warn!(
+                "--assume-gas {assumed} is below the {needed} intrinsic gas of a {chunk} byte chunk, transactions will likely fail"
+            );
+        }
+    }
 
     //let max_txs_per_block = (gas_used_per_block / gas_per_tx).as_u64();
     //tracing::debug!(max_txs_per_block);
 
-    let mut current_data_used = TRIM_BYTES;
     // TODO: Figure out why making a bundle too big fails.
-    let txs_per_block = total_data_size / chunk;
-    // tracing::debug!("txs per block: {}", txs_per_block);
+    let mut data_sizes = match (&params.replay_distribution, &params.size_distribution) {
+        (Some(replay), _) => {
+            let sizes = replay.tx_data_sizes(total_data_size, gas_used_per_block, params.fork);
+            tracing::info!("replaying the capture: {}", replay.describe(&sizes));
+            sizes
+        }
+        (None, Some(distribution)) => distribution.tx_data_sizes(total_data_size),
+        (None, None) => tx_data_sizes(total_data_size, chunk)?,
+    };
+    if data_sizes.is_empty() {
+        return Err(StressError::InvalidConfig(format!(
+            "a {total_data_size} byte fill leaves no calldata to send, raise --fill-pct"
+        )));
+    }
+    if let Some(max_txs) = params.max_txs_per_bundle {
+        if data_sizes.len() > max_txs {
+            data_sizes.truncate(max_txs);
+            let kept: usize = data_sizes.iter().sum();
+            tracing::info!(
+                "--max-txs-per-bundle {max_txs}: bundle cut down to {kept} bytes of calldata, {:.1}% of the block",
+                kept as f64 * 100.0 / block_bytes as f64
+            );
+        }
+    }
+    params.bundle_order.apply(&mut data_sizes);
+    tracing::debug!("signing in {:?} order", params.bundle_order);
+    // tracing::debug!("txs per block: {}", data_sizes.len());
 
-    let default_gas_price = provider.get_gas_price().await?;
+    let mut tip = match params.tip_pct_over_base {
+        Some(pct) => tip_over_base_fee(&provider, pct).await?,
+        None => U256::from(tip_wei),
+    };
+    if let Some(percentile) = params.priority_fee_percentile {
+        let floor = priority_fee_floor(&provider, percentile).await?;
+        if floor > tip {
+            tracing::debug!(
+                "raising tip from {tip} to the p{percentile} priority fee floor {floor}"
+            );
+            tip = floor;
+        }
+    }
 
-    let gas_price = U256::from(tip_wei) + default_gas_price;
-    tracing::debug!("got gas_price {default_gas_price} from provider, increased to {gas_price}");
+    let network_price = match params.gas_price_snapshot {
+        Some(snapshot) => snapshot,
+        None => network_gas_price(params, &provider).await?,
+    };
+    let (gas_price, max_priority_fee) = if params.eip1559 {
+        let base_fee = network_price;
+        let max_fee = base_fee + tip;
+        tracing::debug!("type-2 max fee {max_fee}: next base fee {base_fee} plus the {tip} tip");
+        (max_fee, Some(tip))
+    } else {
+        let default_gas_price = network_price;
+        let gas_price = tip + default_gas_price;
+        tracing::debug!(
+            "got gas_price {default_gas_price} from provider, increased to {gas_price}"
+        );
+        (gas_price, None)
+    };
+    let capped = capped_gas_price(params, gas_price);
+    if capped < gas_price {
+        tracing::info!(
+            "gas price {gas_price} is over --gas-price-cap-wei, capped at {capped} and not escalating further"
+        );
+    }
+    let gas_price = capped;
+    // the priority fee can't be more than the whole max fee
+    let max_priority_fee = max_priority_fee.map(|tip| tip.min(gas_price));
 
     // Construct the bundle
     let mut bundle = BundleRequest::new();
-
-    for _ in 0..txs_per_block {
-        let rlp = get_signed_tx(
-            chain_id, address, receiver, chunk, gas_price, &provider, nonce,
-        )
-        .await?;
+    if let (Some(signer), Some(normal_nonce)) = (&params.normal_tx_signer, normal_nonce) {
+        let rlp = sign_normal_tx(params, signer, normal_nonce, gas_price, max_priority_fee).await?;
         bundle = bundle.push_transaction(rlp);
-        nonce += 1.into();
-        current_data_used += chunk;
     }
 
-    // fill the "remainder" of the block with leftover datasize
-    let remaining_data = total_data_size - current_data_used - TRIM_BYTES;
-    tracing::debug!("signed {txs_per_block} transactions of {chunk} size each, filling remainder {remaining_data}");
-    let last_rlp = get_signed_tx(
-        chain_id,
-        address,
-        receiver,
-        remaining_data,
-        gas_price,
-        &provider,
-        nonce,
-    )
-    .await?;
-    bundle = bundle.push_transaction(last_rlp);
+    for size in &data_sizes {
+        let rlp =
+            get_signed_tx(params, *size, gas_price, max_priority_fee, &provider, nonce).await?;
+        bundle = if params.allow_reverts {
+            bundle.push_revertible_transaction(rlp)
+        } else {
+            bundle.push_transaction(rlp)
+        };
+        nonce += 1.into();
+    }
+    tracing::debug!(
+        "signed {} transactions of up to {chunk} size each",
+        data_sizes.len()
+    );
+    if params.gas_price_jitter_pct > 0.0 {
+        let txs = bundle
+            .transactions()
+            .iter()
+            .map(decode_tx)
+            .collect::<Result<Vec<_>>>()?;
+        if let Some((low, high)) = gas_price_range(&txs) {
+            tracing::info!(
+                "gas price jitter ±{}%: bundle offers {low} to {high} wei per gas",
+                params.gas_price_jitter_pct
+            );
+        }
+    }
+    if params.allow_reverts {
+        tracing::info!(
+            "marked {} transactions as allowed to revert",
+            data_sizes.len()
+        );
+    }
 
     // couldn't get this way working, so instead we just overpay on gas
     // in a legacy transaction within the bundle.  the excess gas price is
@@ -171,3 +885,553 @@ where
 
     Ok(bundle)
 }
+
+/// the bundle's signed transactions, rlp encoded
+pub fn raw_transactions(bundle: &BundleRequest) -> Vec<Bytes> {
+    bundle
+        .transactions()
+        .iter()
+        .map(|tx| match tx {
+            BundleTransaction::Signed(inner) => inner.rlp(),
+            BundleTransaction::Raw(inner) => inner.clone(),
+        })
+        .collect()
+}
+
+/// hashes of the bundle's transactions that may revert, its `revertingTxHashes`, which
+/// `BundleRequest` has no getter for
+pub fn reverting_tx_hashes(bundle: &BundleRequest) -> Vec<TxHash> {
+    serde_json::to_value(bundle)
+        .ok()
+        .and_then(|bundle| serde_json::from_value(bundle["revertingTxHashes"].clone()).ok())
+        .unwrap_or_default()
+}
+
+fn tx_size_bytes(tx: &BundleTransaction) -> usize {
+    match tx {
+        BundleTransaction::Signed(inner) => inner.rlp().len(),
+        BundleTransaction::Raw(inner) => inner.len(),
+    }
+}
+
+/// total size of the signed transactions in the bundle, in bytes
+pub fn bundle_size_bytes(bundle: &BundleRequest) -> usize {
+    bundle.transactions().iter().map(tx_size_bytes).sum()
+}
+
+/// calldata bytes carried by the bundle's transactions, not counting the rest of the encoding
+pub fn bundle_calldata_bytes(bundle: &BundleRequest) -> Result<usize> {
+    Ok(bundle_tx_data_sizes(bundle)?.iter().sum())
+}
+
+/// value sent by all of the bundle's transactions together
+pub fn bundle_value(bundle: &BundleRequest) -> Result<U256> {
+    let mut total = U256::zero();
+    for tx in bundle.transactions() {
+        total += decode_tx(tx)?.value;
+    }
+    Ok(total)
+}
+
+/// calldata size of each transaction in `bundle`
+pub fn bundle_tx_data_sizes(bundle: &BundleRequest) -> Result<Vec<usize>> {
+    bundle
+        .transactions()
+        .iter()
+        .map(|tx| Ok(decode_tx(tx)?.input.len()))
+        .collect()
+}
+
+fn decode_tx(tx: &BundleTransaction) -> Result<Transaction> {
+    match tx {
+        BundleTransaction::Signed(inner) => Ok((**inner).clone()),
+        BundleTransaction::Raw(inner) => decode_raw(inner),
+    }
+}
+
+/// decode a signed transaction, including the set-code ones ethers 1.0 can't.  only those
+/// come with `from` filled in.
+pub fn decode_raw(raw: &[u8]) -> Result<Transaction> {
+    match raw.first() {
+        Some(&crate::set_code::TX_TYPE) => crate::set_code::decode(raw),
+        _ => Ok(ethers::utils::rlp::decode::<Transaction>(raw)?),
+    }
+}
+
+/// rebuild `bundle` without the transactions at the indices in `drop`.  the survivors keep
+/// their calldata, gas and gas price but are re-signed with consecutive nonces starting at
+/// `nonce`, otherwise everything after the first dropped transaction would have a nonce gap.
+pub async fn drop_transactions<M: Middleware>(
+    params: &TxParams,
+    provider: M,
+    bundle: &BundleRequest,
+    drop: &BTreeSet<usize>,
+    mut nonce: U256,
+) -> Result<BundleRequest>
+where
+    M::Error: 'static,
+{
+    let mut kept = BundleRequest::new();
+    for (i, tx) in bundle.transactions().iter().enumerate() {
+        if drop.contains(&i) {
+            continue;
+        }
+        let tx = decode_tx(tx)?;
+        let request = TransactionRequest::new()
+            .chain_id(params.chain_id)
+            .value(tx.value)
+            .from(params.address)
+            .to(params.receiver)
+            .data(tx.input)
+            .gas(tx.gas)
+            .nonce(nonce);
+        // a decoded type-2 transaction has no gas price, only its fees
+        let tx = match tx.max_fee_per_gas {
+            Some(max_fee) => typed_tx(request.gas_price(max_fee), tx.max_priority_fee_per_gas),
+            None => typed_tx(request.gas_price(tx.gas_price.unwrap_or_default()), None),
+        };
+        let signature = provider
+            .sign_transaction(&tx, params.address)
+            .await
+            .map_err(StressError::rpc)?;
+        kept = kept.push_transaction(tx.rlp_signed(&signature));
+        nonce += 1.into();
+    }
+    Ok(kept)
+}
+
+/// re-sign `tx` unchanged but for paying `bump_pct` percent more per gas, so it can replace
+/// the original at the same nonce
+pub async fn bump_gas_price(
+    tx: &Transaction,
+    bump_pct: u64,
+    signer: &LocalWallet,
+) -> Result<Bytes> {
+    let bump = |price: U256| price * (100 + bump_pct) / 100;
+    let request = TransactionRequest::new()
+        .chain_id(tx.chain_id.unwrap_or_default().as_u64())
+        .from(signer.address())
+        .to(tx.to.unwrap_or_default())
+        .value(tx.value)
+        .data(tx.input.clone())
+        .gas(tx.gas)
+        .nonce(tx.nonce);
+    let request = match tx.max_fee_per_gas {
+        Some(max_fee) => typed_tx(
+            request.gas_price(bump(max_fee)),
+            Some(bump(tx.max_priority_fee_per_gas.unwrap_or_default())),
+        ),
+        None => typed_tx(
+            request.gas_price(bump(tx.gas_price.unwrap_or_default())),
+            None,
+        ),
+    };
+    let signature = signer
+        .sign_transaction(&request)
+        .await
+        .map_err(StressError::rpc)?;
+    Ok(request.rlp_signed(&signature))
+}
+
+/// fail loudly if the bundle we actually built is below `min_fill_pct` of the block, rather
+/// than silently submitting a partially filled one.
+pub fn check_min_fill(
+    bundle: &BundleRequest,
+    min_fill_pct: u8,
+    chunk_size: usize,
+    gas_limit: U256,
+    fork: Fork,
+) -> Result<()> {
+    let size = bundle_size_bytes(bundle);
+    let block_bytes = block_data_bytes(gas_limit, fork);
+    let actual_pct = size as f64 * 100.0 / block_bytes as f64;
+    if actual_pct < min_fill_pct as f64 {
+        return Err(StressError::InvalidConfig(format!(
+            "bundle is {size} bytes ({actual_pct:.2}% of the {block_bytes} byte block), below \
+             --min-fill-pct {min_fill_pct}. the {TRIM_BYTES} byte per-transaction trim and the \
+             {chunk_size} byte chunk packing can't reach the target; raise --fill-pct or change \
+             --chunk-size"
+        )));
+    }
+    Ok(())
+}
+
+/// split a bundle whose transactions add up to more than `max_bytes` into consecutive
+/// sub-bundles that each fit, keeping the transactions (and so the nonces) in order.  a single
+/// transaction bigger than `max_bytes` still goes out alone.
+pub fn split_bundle(bundle: &BundleRequest, max_bytes: usize) -> Vec<BundleRequest> {
+    let reverting = reverting_tx_hashes(bundle);
+    let mut parts = Vec::new();
+    let mut part = BundleRequest::new();
+    let mut part_size = 0;
+    for (tx, hash) in bundle
+        .transactions()
+        .iter()
+        .zip(bundle.transaction_hashes())
+    {
+        let size = tx_size_bytes(tx);
+        if part_size + size > max_bytes && !part.transactions().is_empty() {
+            parts.push(part);
+            part = BundleRequest::new();
+            part_size = 0;
+        }
+        part = if reverting.contains(&hash) {
+            part.push_revertible_transaction(tx.clone())
+        } else {
+            part.push_transaction(tx.clone())
+        };
+        part_size += size;
+    }
+    if !part.transactions().is_empty() {
+        parts.push(part);
+    }
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_chunks_plus_remainder() {
+        let chunk = chunk_calldata_bytes(128 * KB);
+        let sizes = tx_data_sizes(BLOCK_DATA_BYTES * 80 / 100, chunk).unwrap();
+        assert_eq!(sizes.len(), 13);
+        assert!(sizes[..12].iter().all(|size| *size == chunk));
+        assert!(sizes[12] > 0 && sizes[12] < chunk);
+    }
+
+    #[test]
+    fn tiny_remainder_is_dropped() {
+        let chunk = chunk_calldata_bytes(KB);
+        let sizes = tx_data_sizes(chunk + TRIM_BYTES, chunk).unwrap();
+        assert_eq!(sizes, vec![chunk]);
+    }
+
+    #[test]
+    fn fallback_gas_prices_zero_bytes_lower() {
+        let data = [0, 0, 1, 0xff];
+        assert_eq!(
+            calldata_gas(&data, Fork::Cancun),
+            U256::from(21_000 + 2 * 4 + 2 * 16)
+        );
+        assert_eq!(
+            calldata_gas(&data, Fork::Prague),
+            U256::from(21_000 + 2 * 10 + 2 * 40)
+        );
+        assert_eq!(calldata_gas(&[], Fork::PreCancun), U256::from(21_000));
+
+        // only size rejections fall back, reverts and transport errors don't
+        assert!(is_size_rejection(&"oversized data"));
+        assert!(is_size_rejection(&"exceeds block gas limit"));
+        assert!(!is_size_rejection(&"execution reverted"));
+        assert!(!is_size_rejection(
+            &"error sending request: connection refused"
+        ));
+    }
+
+    /// throwaway key, only ever used in tests
+    fn test_wallet() -> LocalWallet {
+        "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318"
+            .parse()
+            .unwrap()
+    }
+
+    fn test_params(wallet: &LocalWallet) -> TxParams {
+        TxParams {
+            chain_id: 5,
+            address: wallet.address(),
+            receiver: Address::repeat_byte(0x48),
+            gas_multiplier: 1.0,
+            gas_cap: U256::from(30_000_000),
+            fork: Fork::Cancun,
+            // skips estimate_gas, so the mocked provider never gets asked anything
+            assume_gas: Some(U256::from(1_000_000)),
+            priority_fee_percentile: None,
+            gas_price_cap: None,
+            tip_pct_over_base: None,
+            payload: Arc::new(crate::payload::Random),
+            bundle_order: BundleOrder::AsConstructed,
+            max_txs_per_bundle: None,
+            size_distribution: None,
+            replay_distribution: None,
+            eip1559: false,
+            value_range: None,
+            gas_price_jitter_pct: 0.0,
+            normal_tx_signer: None,
+            allow_reverts: false,
+            gas_price_snapshot: None,
+            set_code: None,
+            gas_oracle: Arc::new(crate::gas_oracle::Fixed(U256::from(7))),
+        }
+    }
+
+    #[tokio::test]
+    async fn bundle_matches_the_sizing_plan() {
+        let wallet = test_wallet();
+        let params = TxParams {
+            assume_gas: None,
+            ..test_params(&wallet)
+        };
+        let gas_limit = U256::from(30_000_000);
+        let chunk_size = 128 * KB;
+
+        // the plan, worked out by hand: 50% of the 1,875,000 bytes 30M gas pays for at 16 gas
+        // a byte, in full chunks plus a remainder that gives up `TRIM_BYTES` for itself and
+        // for the bundle as a whole
+        let chunk = chunk_size - TRIM_BYTES;
+        let total = 30_000_000 / 16 / 2;
+        assert_eq!(block_data_bytes(gas_limit, Fork::Cancun) / 2, total);
+        let txs_per_block = total / chunk;
+        let remainder = total - txs_per_block * chunk - 2 * TRIM_BYTES;
+        assert_eq!((txs_per_block, remainder), (7, 21496));
+
+        let (provider, mock) = Provider::mocked();
+        // every transaction gets its gas from estimate_gas
+        for _ in 0..=txs_per_block {
+            mock.push(U256::from(500_000)).unwrap();
+        }
+        let client = SignerMiddleware::new(provider, wallet.with_chain_id(5u64));
+        let bundle = construct_bundle(
+            &params,
+            &client,
+            gas_limit,
+            50.0,
+            U256::zero(),
+            None,
+            chunk_size,
+            0,
+        )
+        .await
+        .unwrap();
+
+        let sizes = bundle_tx_data_sizes(&bundle).unwrap();
+        assert_eq!(sizes.len(), txs_per_block + 1);
+        assert!(sizes[..txs_per_block].iter().all(|size| *size == chunk));
+        assert_eq!(sizes[txs_per_block], remainder);
+        for tx in bundle.transactions() {
+            let tx = decode_tx(tx).unwrap();
+            assert_eq!(tx.gas, U256::from(500_000));
+            assert_eq!(tx.gas_price, Some(U256::from(7)));
+        }
+    }
+
+    #[test]
+    fn jitter_stays_within_pct() {
+        let wallet = test_wallet();
+        let mut params = test_params(&wallet);
+        let base = U256::from(1_000_000);
+        assert_eq!(
+            jitter_gas_price(base, None, U256::zero(), &params),
+            (base, None)
+        );
+
+        params.gas_price_jitter_pct = 20.0;
+        let txs: Vec<Transaction> = (0..200)
+            .map(|_| {
+                let (gas_price, tip) = jitter_gas_price(base, None, U256::zero(), &params);
+                assert_eq!(tip, None);
+                Transaction {
+                    gas_price: Some(gas_price),
+                    ..Default::default()
+                }
+            })
+            .collect();
+        let (low, high) = gas_price_range(&txs).unwrap();
+        assert!(low >= U256::from(800_000) && high <= U256::from(1_200_000));
+        assert!(low < high);
+        assert_eq!(gas_price_range(&[]), None);
+
+        // type-2: only the 400k tip moves, the 600k base fee part of the max fee stays
+        for _ in 0..200 {
+            let tip = U256::from(400_000);
+            let (max_fee, jittered) = jitter_gas_price(base, Some(tip), U256::zero(), &params);
+            let jittered = jittered.unwrap();
+            assert!(jittered >= U256::from(320_000) && jittered <= U256::from(480_000));
+            assert_eq!(max_fee, U256::from(600_000) + jittered);
+        }
+    }
+
+    #[test]
+    fn jitter_never_goes_under_the_base_fee() {
+        let wallet = test_wallet();
+        let mut params = test_params(&wallet);
+        params.gas_price_jitter_pct = 50.0;
+        let (base, floor) = (U256::from(1_000_000), U256::from(900_000));
+        let prices: Vec<U256> = (0..200)
+            .map(|_| jitter_gas_price(base, None, floor, &params).0)
+            .collect();
+        assert!(prices.iter().all(|price| *price >= floor));
+        // draws under the floor are raised to it, the rest are left alone
+        assert!(prices.contains(&floor));
+        assert!(prices.iter().any(|price| *price > base));
+    }
+
+    #[tokio::test]
+    async fn set_code_tx_round_trips_through_decode_raw() {
+        let wallet = test_wallet();
+        let mut params = test_params(&wallet);
+        let authorization = Authorization {
+            chain_id: U256::from(5),
+            address: Address::repeat_byte(0xaa),
+            nonce: U64::from(1),
+            y_parity: U64::zero(),
+            r: U256::from(3),
+            s: U256::from(4),
+        };
+        params.set_code = Some(SetCode {
+            signer: wallet.clone(),
+            authorizations: vec![authorization],
+        });
+        let (provider, _mock) = Provider::mocked();
+        let client = SignerMiddleware::new(provider, wallet.clone().with_chain_id(5u64));
+
+        let rlp = get_signed_tx(&params, 1000, U256::from(7), None, &client, U256::from(42))
+            .await
+            .unwrap();
+        assert_eq!(rlp[0], crate::set_code::TX_TYPE);
+        let tx = decode_raw(&rlp).unwrap();
+
+        assert_eq!(tx.from, wallet.address());
+        assert_eq!(tx.transaction_type, Some(U64::from(4)));
+        assert_eq!(tx.nonce, U256::from(42));
+        assert_eq!(tx.chain_id, Some(U256::from(params.chain_id)));
+        assert_eq!(tx.to, Some(params.receiver));
+        assert_eq!(tx.input.len(), 1000);
+        // `assume_gas` is taken as is
+        assert_eq!(tx.gas, U256::from(1_000_000));
+        // without a tip the whole gas price is the priority fee
+        assert_eq!(tx.max_fee_per_gas, Some(U256::from(7)));
+        assert_eq!(tx.max_priority_fee_per_gas, Some(U256::from(7)));
+    }
+
+    #[tokio::test]
+    async fn signed_tx_round_trips_through_rlp() {
+        let wallet = test_wallet();
+        let params = test_params(&wallet);
+        let (provider, _mock) = Provider::mocked();
+        let client = SignerMiddleware::new(provider, wallet.clone().with_chain_id(5u64));
+
+        let rlp = get_signed_tx(&params, 1000, U256::from(7), None, &client, U256::from(42))
+            .await
+            .unwrap();
+        let tx = ethers::utils::rlp::decode::<Transaction>(&rlp).unwrap();
+
+        assert_eq!(tx.recover_from().unwrap(), wallet.address());
+        assert_eq!(tx.nonce, U256::from(42));
+        assert_eq!(tx.chain_id, Some(U256::from(5)));
+        assert_eq!(tx.to, Some(params.receiver));
+        assert_eq!(tx.input.len(), 1000);
+        assert_eq!(tx.gas, U256::from(1_000_000));
+        assert_eq!(tx.gas_price, Some(U256::from(7)));
+
+        let rlp = get_signed_tx(
+            &params,
+            1000,
+            U256::from(7),
+            Some(U256::from(2)),
+            &client,
+            U256::from(43),
+        )
+        .await
+        .unwrap();
+        let tx = ethers::utils::rlp::decode::<Transaction>(&rlp).unwrap();
+
+        assert_eq!(tx.recover_from().unwrap(), wallet.address());
+        assert_eq!(tx.transaction_type, Some(U64::from(2)));
+        assert_eq!(tx.nonce, U256::from(43));
+        assert_eq!(tx.max_fee_per_gas, Some(U256::from(7)));
+        assert_eq!(tx.max_priority_fee_per_gas, Some(U256::from(2)));
+
+        let rlp = sign_normal_tx(&params, &wallet, U256::from(3), U256::from(7), None)
+            .await
+            .unwrap();
+        let tx = ethers::utils::rlp::decode::<Transaction>(&rlp).unwrap();
+
+        assert_eq!(tx.recover_from().unwrap(), wallet.address());
+        assert_eq!(tx.nonce, U256::from(3));
+        assert_eq!(tx.value, U256::from(NORMAL_TX_VALUE_WEI));
+        assert_eq!(tx.gas, U256::from(TRANSFER_GAS));
+        assert!(tx.input.is_empty());
+
+        let rlp = get_signed_tx(
+            &params,
+            1000,
+            U256::from(100),
+            None,
+            &client,
+            U256::from(44),
+        )
+        .await
+        .unwrap();
+        let original = ethers::utils::rlp::decode::<Transaction>(&rlp).unwrap();
+        let rlp = bump_gas_price(&original, 10, &wallet).await.unwrap();
+        let tx = ethers::utils::rlp::decode::<Transaction>(&rlp).unwrap();
+
+        assert_eq!(tx.recover_from().unwrap(), wallet.address());
+        assert_eq!(tx.nonce, U256::from(44));
+        assert_eq!(tx.gas_price, Some(U256::from(110)));
+        assert_eq!(tx.input, original.input);
+        assert_eq!(tx.chain_id, Some(U256::from(5)));
+    }
+
+    #[tokio::test]
+    async fn dropping_keeps_type_2_fees() {
+        let wallet = test_wallet();
+        let params = test_params(&wallet);
+        let (provider, _mock) = Provider::mocked();
+        let client = SignerMiddleware::new(provider, wallet.clone().with_chain_id(5u64));
+
+        let mut bundle = BundleRequest::new();
+        for nonce in 0..3 {
+            let rlp = get_signed_tx(
+                &params,
+                1000,
+                U256::from(7),
+                Some(U256::from(2)),
+                &client,
+                U256::from(nonce),
+            )
+            .await
+            .unwrap();
+            bundle = bundle.push_transaction(rlp);
+        }
+        let kept = drop_transactions(&params, &client, &bundle, &BTreeSet::from([1]), 10.into())
+            .await
+            .unwrap();
+
+        assert_eq!(kept.transactions().len(), 2);
+        for (i, tx) in kept.transactions().iter().enumerate() {
+            let tx = decode_tx(tx).unwrap();
+            assert_eq!(tx.recover_from().unwrap(), wallet.address());
+            assert_eq!(tx.transaction_type, Some(U64::from(2)));
+            assert_eq!(tx.nonce, U256::from(10 + i));
+            assert_eq!(tx.max_fee_per_gas, Some(U256::from(7)));
+            assert_eq!(tx.max_priority_fee_per_gas, Some(U256::from(2)));
+        }
+    }
+
+    #[test]
+    fn split_keeps_reverting_transactions() {
+        let revertible = Bytes::from(vec![1, 2, 3]);
+        let bundle = BundleRequest::new()
+            .push_revertible_transaction(revertible.clone())
+            .push_transaction(Bytes::from(vec![4, 5, 6]));
+        assert_eq!(
+            reverting_tx_hashes(&bundle),
+            vec![TxHash::from(ethers::utils::keccak256(&revertible))]
+        );
+
+        let parts = split_bundle(&bundle, 3);
+        assert_eq!(parts.len(), 2);
+        assert_eq!(reverting_tx_hashes(&parts[0]), reverting_tx_hashes(&bundle));
+        assert!(reverting_tx_hashes(&parts[1]).is_empty());
+    }
+
+    #[test]
+    fn degenerate_small_fill_errors() {
+        let chunk = chunk_calldata_bytes(128 * KB);
+        assert!(tx_data_sizes(2 * TRIM_BYTES, chunk).is_err());
+        assert!(tx_data_sizes(0, chunk).is_err());
+    }
+}
@@ -0,0 +1,270 @@
+use ethers::types::U256;
+use serde::{Serialize, Serializer};
+
+use crate::bundle_builder::{intrinsic_gas, validate_chunk_size, Fork, TRIM_BYTES};
+
+/// `--size-distribution`: transaction chunk sizes, each with the share of transactions that
+/// should have it, e.g. `10%:4kb,60%:32kb,30%:128kb`.  shares have to add up to 100%.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SizeDistribution {
+    spec: String,
+    /// (share in percent, chunk size in bytes)
+    buckets: Vec<(f64, usize)>,
+}
+
+impl SizeDistribution {
+    pub fn parse(spec: &str) -> Result<Self, String> {
+        let mut buckets = Vec::new();
+        for bucket in spec.split(',') {
+            let (share, size) = bucket
+                .split_once(':')
+                .ok_or_else(|| format!("each bucket should be PCT%:SIZE: {bucket}"))?;
+            let share = share
+                .trim()
+                .trim_end_matches('%')
+                .parse::<f64>()
+                .map_err(|e| format!("{e}: {bucket}"))?;
+            if share <= 0.0 || !share.is_finite() {
+                return Err(format!("bucket share must be above 0%: {bucket}"));
+            }
+            let size = crate::data_size(size)?;
+            validate_chunk_size(size, false).map_err(|e| e.to_string())?;
+            buckets.push((share, size));
+        }
+        let total: f64 = buckets.iter().map(|(share, _)| share).sum();
+        if (total - 100.0).abs() > 1e-6 {
+            return Err(format!(
+                "bucket shares add up to {total}%, not 100%: {spec}"
+            ));
+        }
+        Ok(Self {
+            spec: spec.to_string(),
+            buckets,
+        })
+    }
+
+    /// draw one chunk size
+    fn sample(&self) -> usize {
+        let mut pick = fastrand::f64() * 100.0;
+        for (share, size) in &self.buckets {
+            if pick < *share {
+                return *size;
+            }
+            pick -= share;
+        }
+        // only reachable through float rounding
+        self.buckets[self.buckets.len() - 1].1
+    }
+
+    /// calldata sizes of transactions with sampled chunk sizes that take up `total_data_size`
+    /// between them, the last one cut down to whatever is left
+    pub fn tx_data_sizes(&self, total_data_size: usize) -> Vec<usize> {
+        let mut sizes = Vec::new();
+        let mut left = total_data_size;
+        while left > TRIM_BYTES {
+            let chunk_size = self.sample().min(left);
+            sizes.push(chunk_size - TRIM_BYTES);
+            left -= chunk_size;
+        }
+        sizes
+    }
+
+    /// how many of the transactions with calldata `sizes` came from each bucket, as a share,
+    /// e.g. `4096b 9.5%, 32768b 61.9%, 131072b 28.6%, 2 cut short`
+    pub fn describe(&self, sizes: &[usize]) -> String {
+        let mut counts = vec![0; self.buckets.len()];
+        let mut cut_short = 0;
+        for size in sizes {
+            match self
+                .buckets
+                .iter()
+                .position(|(_, chunk_size)| chunk_size - TRIM_BYTES == *size)
+            {
+                Some(bucket) => counts[bucket] += 1,
+                None => cut_short += 1,
+            }
+        }
+        let total = sizes.len().max(1) as f64;
+        let mut parts: Vec<String> = self
+            .buckets
+            .iter()
+            .zip(&counts)
+            .map(|((_, size), count)| format!("{size}b {:.1}%", *count as f64 * 100.0 / total))
+            .collect();
+        parts.push(format!("{cut_short} cut short"));
+        parts.join(", ")
+    }
+}
+
+impl Serialize for SizeDistribution {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.spec)
+    }
+}
+
+/// `--replay-distribution`: calldata sizes captured from real blocks, one `size,count` line per
+/// size (in bytes), replayed as bundle transactions of exactly those sizes
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayDistribution {
+    path: String,
+    /// (calldata size in bytes, how many transactions had it)
+    counts: Vec<(usize, usize)>,
+}
+
+impl ReplayDistribution {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        let counts = Self::parse_counts(&text).map_err(|e| format!("{path}: {e}"))?;
+        Ok(Self {
+            path: path.to_string(),
+            counts,
+        })
+    }
+
+    /// `size,count` lines; blank lines, `#` comments and a `size,count` header are skipped
+    fn parse_counts(text: &str) -> Result<Vec<(usize, usize)>, String> {
+        let mut counts = Vec::new();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') || line == "size,count" {
+                continue;
+            }
+            let (size, count) = line
+                .split_once(',')
+                .ok_or_else(|| format!("each line should be SIZE,COUNT: {line}"))?;
+            let size = size.trim().parse().map_err(|e| format!("{e}: {line}"))?;
+            let count = count.trim().parse().map_err(|e| format!("{e}: {line}"))?;
+            if count > 0 {
+                counts.push((size, count));
+            }
+        }
+        if counts.is_empty() {
+            return Err("no transactions to replay".to_string());
+        }
+        Ok(counts)
+    }
+
+    /// the captured transactions' calldata sizes in random order, repeated as often as needed,
+    /// taking each one that still fits in `total_data_size` bytes (with `TRIM_BYTES` for each)
+    /// and `gas_budget` gas
+    pub fn tx_data_sizes(
+        &self,
+        total_data_size: usize,
+        gas_budget: U256,
+        fork: Fork,
+    ) -> Vec<usize> {
+        let mut captured: Vec<usize> = self
+            .counts
+            .iter()
+            .flat_map(|(size, count)| std::iter::repeat_n(*size, *count))
+            .collect();
+        let mut sizes = Vec::new();
+        let (mut bytes_left, mut gas_left) = (total_data_size, gas_budget);
+        loop {
+            fastrand::shuffle(&mut captured);
+            let before = sizes.len();
+            for size in &captured {
+                let gas = intrinsic_gas(*size, fork);
+                if size + TRIM_BYTES <= bytes_left && gas <= gas_left {
+                    sizes.push(*size);
+                    bytes_left -= size + TRIM_BYTES;
+                    gas_left -= gas;
+                }
+            }
+            if sizes.len() == before {
+                return sizes;
+            }
+        }
+    }
+
+    /// how closely calldata `sizes` follow the captured distribution, e.g. `120 transactions,
+    /// 96.3% like the capture`: 100% less the share of transactions that would have to change
+    /// size to match it (the total variation distance)
+    pub fn describe(&self, sizes: &[usize]) -> String {
+        let captured: usize = self.counts.iter().map(|(_, count)| count).sum();
+        let total = sizes.len().max(1) as f64;
+        let mut distance = 0.0;
+        let mut matched = 0;
+        for (size, count) in &self.counts {
+            let replayed = sizes.iter().filter(|s| *s == size).count();
+            matched += replayed;
+            distance += (*count as f64 / captured as f64 - replayed as f64 / total).abs();
+        }
+        // sizes that aren't in the capture at all
+        distance += (sizes.len() - matched) as f64 / total;
+        format!(
+            "{} transactions, {:.1}% like the capture",
+            sizes.len(),
+            100.0 - distance * 50.0
+        )
+    }
+}
+
+impl Serialize for ReplayDistribution {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle_builder::KB;
+
+    #[test]
+    fn shares_must_add_up_to_100() {
+        let distribution = SizeDistribution::parse("10%:4kb,60%:32kb,30%:128kb").unwrap();
+        assert_eq!(
+            distribution.buckets,
+            vec![(10.0, 4 * KB), (60.0, 32 * KB), (30.0, 128 * KB)]
+        );
+        assert!(SizeDistribution::parse("10%:4kb,60%:32kb").is_err());
+        assert!(SizeDistribution::parse("0%:4kb,100%:32kb").is_err());
+        assert!(SizeDistribution::parse("100%:100b").is_err());
+        assert!(SizeDistribution::parse("100").is_err());
+    }
+
+    #[test]
+    fn sampled_sizes_fill_the_total() {
+        let distribution = SizeDistribution::parse("50%:4kb,50%:32kb").unwrap();
+        let total = 1024 * KB;
+        let sizes = distribution.tx_data_sizes(total);
+        let used: usize = sizes.iter().map(|size| size + TRIM_BYTES).sum();
+        assert!(used <= total && total - used <= TRIM_BYTES);
+        let (last, full) = sizes.split_last().unwrap();
+        assert!(full
+            .iter()
+            .all(|size| *size == 4 * KB - TRIM_BYTES || *size == 32 * KB - TRIM_BYTES));
+        assert!(*last <= 32 * KB - TRIM_BYTES);
+    }
+
+    #[test]
+    fn replay_fits_the_gas_budget() {
+        let counts =
+            ReplayDistribution::parse_counts("size,count\n# a capture\n100,3\n0,1\n").unwrap();
+        assert_eq!(counts, vec![(100, 3), (0, 1)]);
+        assert!(ReplayDistribution::parse_counts("100").is_err());
+        assert!(ReplayDistribution::parse_counts("100,0").is_err());
+
+        let replay = ReplayDistribution {
+            path: "capture.csv".to_string(),
+            counts,
+        };
+        // gas for every transaction twice over, and plenty of bytes
+        let budget = (intrinsic_gas(100, Fork::Cancun) * 3 + intrinsic_gas(0, Fork::Cancun)) * 2;
+        let sizes = replay.tx_data_sizes(1024 * KB, budget, Fork::Cancun);
+        assert_eq!(sizes.len(), 8);
+        assert_eq!(sizes.iter().filter(|size| **size == 100).count(), 6);
+        assert_eq!(
+            replay.describe(&sizes),
+            "8 transactions, 100.0% like the capture"
+        );
+
+        // the gas budget runs out first
+        let sizes = replay.tx_data_sizes(1024 * KB, U256::from(21_000), Fork::Cancun);
+        assert_eq!(sizes, vec![0]);
+        assert_eq!(
+            replay.describe(&sizes),
+            "1 transactions, 25.0% like the capture"
+        );
+    }
+}
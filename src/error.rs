@@ -0,0 +1,38 @@
+use ethers::prelude::*;
+use std::error::Error;
+
+/// what can go wrong building and submitting the stress transactions, for callers that want
+/// to handle some of it rather than just report it.  the binary wraps it in `eyre`.
+#[derive(Debug, thiserror::Error)]
+pub enum StressError {
+    /// the sizes, fill or fees asked for can't make a valid transaction or bundle
+    #[error("{0}")]
+    InvalidConfig(String),
+    #[error("{address:?} has {balance} wei, not enough to pay for gas")]
+    InsufficientBalance { address: Address, balance: U256 },
+    #[error("relay rejected the bundle: {0}")]
+    RelayRejected(Box<dyn Error + Send + Sync>),
+    /// anything failing on the way to or from the rpc, signing included
+    #[error(transparent)]
+    RpcTransport(Box<dyn Error + Send + Sync>),
+    #[error("{sender:?} is at nonce {current}, but its transaction has nonce {nonce}")]
+    NonceGap {
+        sender: Address,
+        current: U256,
+        nonce: U256,
+    },
+    #[error("malformed signed transaction: {0}")]
+    Decode(#[from] ethers::utils::rlp::DecoderError),
+}
+
+impl StressError {
+    pub fn rpc(err: impl Error + Send + Sync + 'static) -> Self {
+        Self::RpcTransport(Box::new(err))
+    }
+
+    pub fn relay(err: impl Error + Send + Sync + 'static) -> Self {
+        Self::RelayRejected(Box::new(err))
+    }
+}
+
+pub type Result<T, E = StressError> = std::result::Result<T, E>;
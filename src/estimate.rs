@@ -0,0 +1,169 @@
+use ethers::prelude::*;
+use eyre::Result;
+
+use crate::bundle_builder::{
+    block_data_bytes, capped_gas_price, chunk_calldata_bytes, intrinsic_gas, network_gas_price,
+    tx_data_sizes, Fork, TxParams,
+};
+
+/// what it takes to fill a block to some fill, in basis points: the gas its transactions
+/// need at the least, and how many of them there are
+fn block_needs(
+    gas_limit: U256,
+    fill_bps: usize,
+    chunk: usize,
+    fork: Fork,
+) -> Option<(U256, usize)> {
+    let total_data_size = fill_bps * block_data_bytes(gas_limit, fork) / 10_000;
+    let sizes = tx_data_sizes(total_data_size, chunk).ok()?;
+    let gas = sizes
+        .iter()
+        .fold(U256::zero(), |gas, size| gas + intrinsic_gas(*size, fork));
+    Some((gas, sizes.len()))
+}
+
+/// the highest fill, in basis points up to 100%, whose transactions fit under `gas_limit` and
+/// cost at most `budget` wei per block, paying `gas_price` per gas and up to `value` wei each
+fn max_fill_bps(
+    gas_limit: U256,
+    chunk: usize,
+    fork: Fork,
+    gas_price: U256,
+    value: U256,
+    budget: U256,
+) -> Option<usize> {
+    (1..=10_000).rev().find(|fill_bps| {
+        block_needs(gas_limit, *fill_bps, chunk, fork).is_some_and(|(gas, txs)| {
+            gas <= gas_limit && gas * gas_price + value * U256::from(txs) <= budget
+        })
+    })
+}
+
+/// print what a run of `--blocks` bundles at `--fill-pct` would cost at the current gas price,
+/// how long `balance` would last at that fill, and the highest fill it can pay for over all
+/// the blocks.  costs are lower bounds: every transaction is priced at its intrinsic gas.
+pub async fn run<M: Middleware>(
+    params: &TxParams,
+    provider: &M,
+    gas_limit: U256,
+    balance: U256,
+    opts: &crate::Opts,
+) -> Result<()>
+where
+    M::Error: 'static,
+{
+    let (fill_pct, blocks) = (opts.fill_pct, opts.blocks);
+    let chunk = chunk_calldata_bytes(opts.chunk_size);
+    let network_price = match params.gas_price_snapshot {
+        Some(snapshot) => snapshot,
+        None => network_gas_price(params, provider).await?,
+    };
+    let gas_price = capped_gas_price(params, network_price + opts.tip_wei);
+    let value = U256::from(params.value_range.map_or(0, |(_, max)| max));
+    println!(
+        "block gas limit {gas_limit}, network gas price {network_price} wei, paying {gas_price} wei per gas"
+    );
+
+    let fill_bps = (fill_pct * 100.0).round() as usize;
+    match block_needs(gas_limit, fill_bps, chunk, params.fork) {
+        Some((gas, txs)) => {
+            let cost = gas * gas_price + value * U256::from(txs);
+            let lasts = if cost.is_zero() {
+                "indefinitely".to_string()
+            } else {
+                format!("{} blocks", balance / cost)
+            };
+            println!(
+                "--fill-pct {fill_pct}%: {txs} transactions, {gas} gas and {} ETH per block; {} ETH lasts {lasts}",
+                ethers::utils::format_units(cost, "ether")?,
+                ethers::utils::format_units(balance, "ether")?
+            );
+            if gas > gas_limit {
+                println!("that's more gas than the block has, the bundle can't land");
+            }
+        }
+        None => println!("--fill-pct {fill_pct}% leaves no calldata to send"),
+    }
+
+    let budget = balance / U256::from(blocks.max(1));
+    match max_fill_bps(gas_limit, chunk, params.fork, gas_price, value, budget) {
+        Some(max) => println!(
+            "with your balance you can fill at most {:.2}% for {blocks} blocks",
+            max as f64 / 100.0
+        ),
+        None => println!("your balance can't pay for any fill for {blocks} blocks"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_is_capped_by_budget_and_gas_limit() {
+        let gas_limit = U256::from(30_000_000);
+        let chunk = chunk_calldata_bytes(128 * 1024);
+        let (full_gas, txs) = block_needs(gas_limit, 10_000, chunk, Fork::Cancun).unwrap();
+        assert_eq!(txs, 15);
+
+        // plenty of eth: only the gas limit caps it, the 21000 gas per transaction leaving a
+        // full block's calldata just over 30M gas
+        let plenty = U256::exp10(24);
+        let cancun = max_fill_bps(
+            gas_limit,
+            chunk,
+            Fork::Cancun,
+            U256::one(),
+            U256::zero(),
+            plenty,
+        )
+        .unwrap();
+        assert!(cancun < 10_000);
+        assert!(
+            block_needs(gas_limit, cancun, chunk, Fork::Cancun)
+                .unwrap()
+                .0
+                <= gas_limit
+        );
+        assert!(
+            block_needs(gas_limit, cancun + 1, chunk, Fork::Cancun)
+                .unwrap()
+                .0
+                > gas_limit
+        );
+        assert!(full_gas > gas_limit);
+        // Prague's calldata costs 40 gas a byte, but the fill is a share of what the gas limit
+        // pays for at that price, so it still reaches nearly all of the block
+        let prague = max_fill_bps(
+            gas_limit,
+            chunk,
+            Fork::Prague,
+            U256::one(),
+            U256::zero(),
+            plenty,
+        )
+        .unwrap();
+        assert!(prague > 9_000 && prague < 10_000);
+
+        // a budget of exactly what a 40% fill costs, at 2 wei a gas plus 1 gwei a transaction
+        let (gas, txs) = block_needs(gas_limit, 4_000, chunk, Fork::Cancun).unwrap();
+        let (price, value) = (U256::from(2), U256::exp10(9));
+        let budget = gas * price + value * U256::from(txs);
+        assert_eq!(
+            max_fill_bps(gas_limit, chunk, Fork::Cancun, price, value, budget),
+            Some(4_000)
+        );
+        assert_eq!(
+            max_fill_bps(
+                gas_limit,
+                chunk,
+                Fork::Cancun,
+                price,
+                value,
+                U256::from(1000)
+            ),
+            None
+        );
+    }
+}
@@ -0,0 +1,29 @@
+use serde_json::Value;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// set once at startup by `--events-stdout`
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// write `record` to stdout as a single json line tagged with `event_type`, for programs
+/// wrapping the binary.  human-readable logs stay on stderr.
+pub fn emit(event_type: &str, record: &Value) {
+    if !ENABLED.load(Ordering::Relaxed) {
+        return;
+    }
+    let mut event = serde_json::Map::new();
+    event.insert("event_type".to_string(), Value::from(event_type));
+    if let Value::Object(fields) = record {
+        for (key, value) in fields {
+            if key != "record_type" {
+                event.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    let mut stdout = std::io::stdout().lock();
+    let _res = writeln!(stdout, "{}", Value::Object(event));
+}
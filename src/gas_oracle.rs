@@ -0,0 +1,93 @@
+use async_trait::async_trait;
+use ethers::middleware::gas_oracle::{
+    BlockNative, GasCategory, GasOracle, GasOracleError, ProviderOracle,
+};
+use ethers::prelude::*;
+use serde::Serialize;
+use std::sync::Arc;
+
+/// where the network gas price comes from, picked with `--gas-oracle`.  each is a
+/// `GasOracle`, so another source only has to implement that and get a variant here.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Source {
+    /// the node's `eth_gasPrice`, whatever oracle its client runs
+    Node,
+    /// blocknative's gas price api, at `--blocknative-confidence`
+    Blocknative,
+    /// `--fixed-gas-price-wei`, never changing
+    Fixed,
+}
+
+impl Source {
+    pub fn oracle(
+        self,
+        provider: Arc<Provider<Http>>,
+        blocknative_api_key: Option<&str>,
+        blocknative_confidence: u64,
+        fixed_gas_price_wei: Option<u64>,
+    ) -> eyre::Result<Arc<dyn GasOracle>> {
+        Ok(match self {
+            Source::Node => Arc::new(ProviderOracle::new(provider)),
+            Source::Blocknative => {
+                let api_key = blocknative_api_key
+                    .ok_or_else(|| eyre::eyre!("--gas-oracle blocknative needs an api key"))?;
+                Arc::new(
+                    BlockNative::new(api_key.to_string())
+                        .category(category(blocknative_confidence)?),
+                )
+            }
+            Source::Fixed => {
+                let price = fixed_gas_price_wei
+                    .ok_or_else(|| eyre::eyre!("--gas-oracle fixed needs a gas price"))?;
+                Arc::new(Fixed(U256::from(price)))
+            }
+        })
+    }
+}
+
+/// always the same gas price
+#[derive(Debug)]
+pub struct Fixed(pub U256);
+
+#[async_trait]
+impl GasOracle for Fixed {
+    async fn fetch(&self) -> Result<U256, GasOracleError> {
+        Ok(self.0)
+    }
+
+    async fn estimate_eip1559_fees(&self) -> Result<(U256, U256), GasOracleError> {
+        Err(GasOracleError::Eip1559EstimationNotSupported)
+    }
+}
+
+/// the `GasCategory` ethers asks blocknative for a `confidence` percent price with
+fn category(confidence: u64) -> eyre::Result<GasCategory> {
+    Ok(match confidence {
+        80 => GasCategory::SafeLow,
+        90 => GasCategory::Standard,
+        95 => GasCategory::Fast,
+        99 => GasCategory::Fastest,
+        _ => eyre::bail!("blocknative confidence {confidence} isn't one of 80, 90, 95 or 99"),
+    })
+}
+
+/// parse `--blocknative-confidence`
+pub fn confidence(s: &str) -> Result<u64, String> {
+    let confidence = s.parse::<u64>().map_err(|err| err.to_string())?;
+    category(confidence).map_err(|err| err.to_string())?;
+    Ok(confidence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn fixed_and_confidence() {
+        assert_eq!(Fixed(U256::from(7)).fetch().await.unwrap(), U256::from(7));
+        assert_eq!(confidence("95"), Ok(95));
+        assert!(confidence("50").is_err());
+        assert!(confidence("high").is_err());
+    }
+}
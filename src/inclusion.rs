@@ -0,0 +1,94 @@
+use ethers::prelude::*;
+
+use crate::error::{Result, StressError};
+
+/// how many recent blocks the estimate looks at
+const HISTORY_BLOCKS: u64 = 20;
+
+/// the priority fee percentiles asked of `eth_feeHistory`, 0 to 100 in steps of 10
+const PERCENTILES: [f64; 11] = [
+    0.0, 10.0, 20.0, 30.0, 40.0, 50.0, 60.0, 70.0, 80.0, 90.0, 100.0,
+];
+
+/// the estimated chance, between 0 and 1, that a bundle taking `fill` (0 to 1) of the block's
+/// gas lands tipping `tip` wei per gas.
+///
+/// a builder only takes the bundle if it pays more than what it pushes out.  in a recent block
+/// that used `ratio` of its gas, the bundle displaces `max(0, ratio + fill - 1)` of it, the
+/// cheapest transactions first.  if that's `d` of the block's used gas, the bundle is taken
+/// when `tip` is at least the block's `d`-th percentile priority fee (rounded up to the next
+/// 10th).  the estimate is the fraction of the last `HISTORY_BLOCKS` blocks that would have
+/// taken it.  direct coinbase payments and how builders weigh bundles against the mempool
+/// are left out, so it's a guide for picking a tip rather than a calibrated probability.
+pub async fn estimate<M: Middleware>(provider: &M, tip: U256, fill: f64) -> Result<f64>
+where
+    M::Error: 'static,
+{
+    let history = provider
+        .fee_history(HISTORY_BLOCKS, BlockNumber::Latest, &PERCENTILES)
+        .await
+        .map_err(StressError::rpc)?;
+    Ok(estimate_from(
+        &history.gas_used_ratio,
+        &history.reward,
+        tip,
+        fill,
+    ))
+}
+
+/// `estimate` over each block's gas used ratio and its `PERCENTILES` rewards
+fn estimate_from(gas_used_ratios: &[f64], rewards: &[Vec<U256>], tip: U256, fill: f64) -> f64 {
+    let fill = fill.clamp(0.0, 1.0);
+    let mut blocks = 0;
+    let mut taken = 0;
+    for (ratio, reward) in gas_used_ratios.iter().zip(rewards) {
+        blocks += 1;
+        let displaced = (ratio + fill - 1.0).max(0.0);
+        if displaced <= 0.0 {
+            taken += 1;
+            continue;
+        }
+        let share = (displaced / ratio).min(1.0);
+        let index = ((share * 10.0).ceil() as usize).min(reward.len().saturating_sub(1));
+        if reward.get(index).is_none_or(|fee| tip >= *fee) {
+            taken += 1;
+        }
+    }
+    if blocks == 0 {
+        return 0.0;
+    }
+    taken as f64 / blocks as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn outbidding_what_gets_displaced_lands() {
+        let reward: Vec<U256> = (0..=10u64).map(|i| U256::from(i * 100)).collect();
+        let rewards = vec![reward.clone(), reward];
+
+        // half-full blocks have room for a half-block bundle whatever the tip
+        assert_eq!(estimate_from(&[0.5, 0.4], &rewards, U256::zero(), 0.5), 1.0);
+        // a full block, displacing all of it, needs the top priority fee
+        assert_eq!(
+            estimate_from(&[1.0, 1.0], &rewards, U256::from(999), 1.0),
+            0.0
+        );
+        assert_eq!(
+            estimate_from(&[1.0, 1.0], &rewards, U256::from(1000), 1.0),
+            1.0
+        );
+        // displacing a fifth of one block needs its p20 fee, the other has room
+        assert_eq!(
+            estimate_from(&[1.0, 0.5], &rewards, U256::from(100), 0.2),
+            0.5
+        );
+        assert_eq!(
+            estimate_from(&[1.0, 0.5], &rewards, U256::from(200), 0.2),
+            1.0
+        );
+        assert_eq!(estimate_from(&[], &[], U256::from(200), 0.2), 0.0);
+    }
+}
@@ -1,7 +1,9 @@
 // CLI
-use clap::Parser;
+use clap::{parser::ValueSource, CommandFactory, FromArgMatches, Parser};
+use error::StressError;
 use ethers::prelude::k256::ecdsa::SigningKey;
 use eyre::Result;
+use serde::{Deserialize, Serialize, Serializer};
 use serde_json::{json, Value};
 use std::{thread, time};
 use tracing_subscriber::{filter::EnvFilter, prelude::*};
@@ -9,45 +11,175 @@ use tracing_subscriber::{filter::EnvFilter, prelude::*};
 // Misc
 use chrono::prelude::*;
 use ethers::prelude::*;
-use ethers_flashbots::FlashbotsMiddleware;
+use ethers_flashbots::{
+    BundleHash, BundleRequest, FlashbotsMiddleware, FlashbotsMiddlewareError, PendingBundle,
+    PendingBundleError,
+};
+use flate2::{write::GzEncoder, Compression};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
 use std::fs::OpenOptions;
 use std::io::Write;
-use std::sync::Arc;
-use std::time::Duration;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
 use url::Url;
 
 // local utils
 mod bundle_builder;
+mod distribution;
+mod error;
+mod estimate;
+mod events;
+mod gas_oracle;
+mod inclusion;
+mod metrics;
+mod mev_share;
+mod nonce;
+mod pacing;
+mod pattern;
+mod payload;
+mod pool;
+mod preset;
+mod profile;
+mod progress;
+mod relays;
+mod repl;
+mod replay;
+mod rpc_batch;
+mod schedule;
+mod selftest;
+mod set_code;
+mod simulate;
+mod summary;
+mod wallets;
+mod watch;
 
 /// command line arguments for running the script
-#[derive(Debug, Parser)]
+#[derive(Debug, Parser, Serialize)]
 struct Opts {
+    /// Named scenario setting fill_pct, chunk_size, tip_wei and mempool_txs together.
+    /// Any of those passed explicitly still take precedence.
+    #[arg(long, value_enum)]
+    preset: Option<preset::Preset>,
+
     /// The number of blocks to run the stress test for
     #[arg(default_value = "1", long)]
     blocks: usize,
 
-    /// What % of the block to fill (0-100).
-    #[arg(default_value = "80", long, short, value_parser = clap::value_parser!(u8).range(1..=100))]
-    fill_pct: u8,
+    /// What % of the block to fill, e.g. 80 or 99.5.  Up to 200, where anything over 100
+    /// deliberately builds an over-full bundle.
+    #[arg(default_value = "80", long, short, value_parser = fill_percent)]
+    fill_pct: f64,
+
+    /// Abort instead of submitting if a constructed bundle ends up below this % of the
+    /// block, e.g. because of per-transaction overhead or chunk packing.
+    #[arg(long, value_parser = clap::value_parser!(u8).range(1..=100))]
+    min_fill_pct: Option<u8>,
+
+    /// In bundle mode, pick the chunk size that fits the most calldata under the block gas
+    /// limit (by estimating the transactions' gas) and under `max_bundle_bytes`, lowering
+    /// `fill_pct` to what fits if needed.
+    #[arg(
+        default_value = "false",
+        long,
+        num_args = 0,
+        conflicts_with = "chunk_size"
+    )]
+    auto_chunk: bool,
+
+    /// In bundle mode, give each transaction a chunk size drawn from this histogram instead of
+    /// `chunk_size`, e.g. `10%:4kb,60%:32kb,30%:128kb`.  The shares must add up to 100%.
+    #[arg(
+        long,
+        value_parser = distribution::SizeDistribution::parse,
+        conflicts_with_all = ["mem_pool", "chunk_size", "auto_chunk"]
+    )]
+    size_distribution: Option<distribution::SizeDistribution>,
+
+    /// In bundle mode, replay the calldata sizes in this file of `size,count` lines, e.g.
+    /// captured from mainnet blocks, as far as each block's fill and gas allow.
+    #[arg(
+        long,
+        value_parser = distribution::ReplayDistribution::load,
+        conflicts_with_all = ["mem_pool", "chunk_size", "auto_chunk", "size_distribution"]
+    )]
+    replay_distribution: Option<distribution::ReplayDistribution>,
+
+    /// Never pack more than this many transactions into a bundle; the fill is cut short
+    /// instead.  For finding how many transactions the relay accepts in one bundle.
+    #[arg(long, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    max_txs_per_bundle: Option<usize>,
+
+    /// Order of the transactions within a bundle, by calldata size.  Nonces are assigned in
+    /// that order.
+    #[arg(default_value = "as-constructed", long, value_enum)]
+    bundle_order: bundle_builder::BundleOrder,
+
+    /// Abort if the chain's block gas limit is below this, e.g. to catch a downsized testnet
+    /// before sizing bundles for it.
+    #[arg(long)]
+    min_block_gas_limit: Option<u64>,
 
-    /// How much calldata (in kbytes) to send in each individual transaction.
-    /// Note that mempool is limited to 128 in geth, and higher values required
+    /// Split bundles larger than this many bytes into several sub-bundles targeting the same
+    /// block, to stay under relay size limits.
+    #[arg(long)]
+    max_bundle_bytes: Option<usize>,
+
+    /// How much calldata to send in each individual transaction, e.g. `128kb`, `1mb` or
+    /// `65536b`.  A bare number is kbytes, which is deprecated.
+    /// Note that mempool is limited to 128kb in geth, and higher values required
     /// special white listing from flashbots relay on goerli.
-    #[arg(default_value = "128", long, short)]
+    #[arg(default_value = "128kb", long, short, value_parser = data_size)]
     chunk_size: usize,
 
     /// The HTTP RPC endpoint to submit the transactions to.
     #[arg(long, short, value_parser = http_provider)]
     rpc_url: String,
 
+    /// extra `Name: Value` header to send with every request to `rpc_url`, e.g. an API key.
+    /// can be passed more than once.
+    #[arg(long, value_parser = rpc_header)]
+    #[serde(serialize_with = "redact_headers")]
+    rpc_header: Vec<(HeaderName, HeaderValue)>,
+
     /// The private key for the wallet you'll submit the stress test
     /// transactions with. MUST have enough ETH to cover for the gas.
-    #[arg(long, short)]
+    /// Can be left out with `--mnemonic`, to use the first derived wallet.
+    #[arg(default_value = "", long, short, required_unless_present = "mnemonic")]
+    #[serde(serialize_with = "redact")]
     tx_signer: String,
 
+    /// a BIP-39 mnemonic to derive signers from, at `m/44'/60'/0'/0/<index>` for each of the
+    /// `wallet_index` indices.  without `--tx-signer`, the first one is the tx signer; the rest
+    /// (all of them, with `--tx-signer`) are added to the `--extra-tx-signer`s.
+    #[arg(long)]
+    #[serde(serialize_with = "redact")]
+    mnemonic: Option<String>,
+
+    /// with `--mnemonic`, which wallets to derive: one index `N`, or `A..B` for `A` up to but
+    /// not including `B`.
+    #[arg(default_value = "0", long, requires = "mnemonic", value_parser = wallets::index_range)]
+    wallet_index: std::ops::Range<u32>,
+
+    /// in mempool mode, another private key to spread the transactions over, alongside
+    /// `tx_signer`.  can be passed more than once.
+    #[arg(long)]
+    #[serde(serialize_with = "redact")]
+    extra_tx_signer: Vec<String>,
+
+    /// with `--extra-tx-signer`, whether each signer sends one consecutive slice of the
+    /// transactions or the signers take turns.
+    #[arg(default_value = "contiguous", long, value_enum)]
+    nonce_distribution: schedule::NonceDistribution,
+
     /// The private key for the full-block template bundle signer wallet.
-    /// This is used for reputation within mev-boost.
+    /// This is used for reputation within mev-boost.  A random one is generated
+    /// when left blank.
     #[arg(default_value = "", long, short)]
+    #[serde(serialize_with = "redact")]
     bundle_signer: String,
 
     /// default "tip" is 5gwei.  have noticed that on goerli, inclusion seems to be pretty
@@ -55,6 +187,131 @@ struct Opts {
     #[arg(default_value = "5000000000", long)]
     tip_wei: u64,
 
+    /// tip this percentage of the latest block's base fee, e.g. 10 for 10%, instead of a fixed
+    /// `tip_wei`.
+    #[arg(long, conflicts_with = "tip_wei", value_parser = tip_percent)]
+    tip_pct_over_base: Option<f64>,
+
+    /// vary each transaction's gas price (with `--eip1559` just its tip) randomly by up to
+    /// ± this percent, e.g. 20, to spread a batch across fee levels, but never under the next
+    /// base fee.  0 gives every transaction the same price.
+    #[arg(
+        default_value = "0",
+        long,
+        value_parser = jitter_percent,
+        conflicts_with = "duplicate_nonce"
+    )]
+    gas_price_jitter_pct: f64,
+
+    /// what to fill transaction calldata with.
+    #[arg(default_value = "random", long, value_enum)]
+    data_fill: payload::DataFill,
+
+    /// fill transaction calldata by tiling the template in this file instead of `data_fill`.
+    /// see `pattern::Pattern` for the format.
+    #[arg(long, conflicts_with = "data_fill")]
+    payload_pattern_file: Option<PathBuf>,
+
+    /// in bundle mode, search for the lowest tip that lands: start at `tip_wei`, raise it by
+    /// `discover_step_wei` after every miss, and stop once `discover_streak` bundles in a row
+    /// land at the same tip, reporting it in the summary.  replaces `--blocks`.
+    #[arg(
+        default_value = "false",
+        long,
+        num_args = 0,
+        conflicts_with = "tip_pct_over_base"
+    )]
+    discover_min_tip: bool,
+
+    /// how much `--discover-min-tip` raises the tip by after a miss, in wei.  default 1gwei.
+    #[arg(default_value = "1000000000", long)]
+    discover_step_wei: u64,
+
+    /// how many bundles in a row have to land for `--discover-min-tip` to settle on a tip.
+    #[arg(default_value = "3", long, value_parser = clap::value_parser!(u32).range(1..))]
+    discover_streak: u32,
+
+    /// never sign with a legacy gas price (base price plus tip) above this many wei.  caps the
+    /// max fee of `--eip1559` transactions too.
+    #[arg(long)]
+    gas_price_cap_wei: Option<u64>,
+
+    /// in bundle mode, skip every block whose base fee is over this many wei, resuming once it
+    /// drops back, so a fee spike doesn't burn through the budget.
+    #[arg(long, conflicts_with = "mem_pool")]
+    max_base_fee_wei: Option<u64>,
+
+    /// read the network gas price (with `--eip1559`, the next base fee) once at startup and
+    /// build every bundle's or transaction's gas price on it, so it doesn't vary between
+    /// blocks.  the tip still comes on top.
+    #[arg(default_value = "false", long, num_args = 0)]
+    fixed_gas_snapshot: bool,
+
+    /// where the network gas price (before the tip) comes from: the node's `eth_gasPrice`,
+    /// blocknative's gas api, or `--fixed-gas-price-wei`.  `--eip1559` transactions build on
+    /// the next base fee instead, whichever it is.
+    #[arg(default_value = "node", long, value_enum)]
+    gas_oracle: gas_oracle::Source,
+
+    /// with `--gas-oracle blocknative`, the api key.
+    #[arg(long, required_if_eq("gas_oracle", "blocknative"))]
+    #[serde(serialize_with = "redact")]
+    blocknative_api_key: Option<String>,
+
+    /// with `--gas-oracle blocknative`, the confidence (80, 90, 95 or 99 percent) of landing
+    /// in the next block that the price is for.
+    #[arg(default_value = "90", long, value_parser = gas_oracle::confidence)]
+    blocknative_confidence: u64,
+
+    /// with `--gas-oracle fixed`, the gas price in wei.
+    #[arg(long, required_if_eq("gas_oracle", "fixed"))]
+    fixed_gas_price_wei: Option<u64>,
+
+    /// in bundle mode, sign type-2 transactions: the tip is the max priority fee, and the max
+    /// fee is the next block's base fee plus the tip.
+    #[arg(
+        default_value = "false",
+        long,
+        num_args = 0,
+        conflicts_with = "mem_pool"
+    )]
+    eip1559: bool,
+
+    /// in bundle mode, sign the calldata transactions as this type instead: `7702` for
+    /// EIP-7702 set-code transactions carrying `--authorization-list`, priced like
+    /// `--eip1559`.  only on chains at Prague or later.
+    #[arg(
+        long,
+        value_enum,
+        requires = "authorization_list",
+        conflicts_with_all = ["mem_pool", "exclude_reverting"]
+    )]
+    tx_type: Option<bundle_builder::TxType>,
+
+    /// with `--tx-type 7702`, a JSON list of signed authorizations, each with `chainId`,
+    /// `address`, `nonce`, `yParity`, `r` and `s`, that every transaction carries.
+    #[arg(
+        long,
+        value_parser = set_code::AuthorizationList::load,
+        requires = "tx_type"
+    )]
+    authorization_list: Option<set_code::AuthorizationList>,
+
+    /// in bundle mode, tip at least the median (over the last few blocks) of this percentile of
+    /// priority fees, from `eth_feeHistory`.  `tip_wei` still applies if it's higher.
+    #[arg(long, value_parser = percentile)]
+    priority_fee_percentile: Option<f64>,
+
+    /// multiplier applied to the `estimate_gas` result for every transaction (e.g. 1.2 for 20%
+    /// headroom), capped at the block gas limit.
+    #[arg(default_value = "1.0", long, value_parser = gas_multiplier)]
+    gas_multiplier: f64,
+
+    /// in bundle mode, use this fixed gas limit for every transaction and skip `estimate_gas`,
+    /// saving an RPC round-trip per transaction when building large uniform bundles.
+    #[arg(long)]
+    assume_gas: Option<u64>,
+
     /// do we use mev-boost, or submit via the mempool?
     #[arg(default_value = "false", long, num_args = 0)]
     mem_pool: bool,
@@ -62,6 +319,496 @@ struct Opts {
     /// if using mempool, how many transactions to submit in parallel?  (with appropriate nonce increment)
     #[arg(default_value = "64", long)]
     mempool_txs: usize,
+
+    /// if using mempool, send the transactions in waves of this many, waiting for the node to
+    /// accept each wave before starting the next.  all at once if unset.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    batch_size: Option<u32>,
+
+    /// if using mempool, send the transactions as json-rpc batches of this many
+    /// `eth_sendRawTransaction` calls, one http request per batch.  falls back to a request
+    /// per transaction if the node doesn't take batches.
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with_all = ["batch_size", "conditional_block_range"]
+    )]
+    rpc_batch_size: Option<u32>,
+
+    /// if using mempool, at most this many sends are in flight to the rpc at once, so a
+    /// rate-limited endpoint isn't flooded with all `mempool_txs` together.
+    #[arg(default_value = "16", long, value_parser = clap::value_parser!(u32).range(1..))]
+    send_concurrency: u32,
+
+    /// in bundle mode, at most this many bundle submissions (each sub-bundle to each relay)
+    /// are in flight at once, so many relays or `--max-bundle-bytes` parts don't swamp them.
+    #[arg(default_value = "4", long, value_parser = clap::value_parser!(u32).range(1..))]
+    max_concurrent_bundles: u32,
+
+    /// base the nonce on the latest confirmed state or the node's pending view.  `latest`
+    /// avoids collisions with other senders' pending transactions on some RPCs.
+    #[arg(default_value = "pending", long, value_enum)]
+    nonce_block: NonceBlock,
+
+    /// if using mempool, submit via `eth_sendRawTransactionConditional` so the transactions are
+    /// only included within this block range, given as MIN:MAX.  falls back to a plain
+    /// `eth_sendRawTransaction` if the node doesn't support it.
+    #[arg(long, value_parser = block_range)]
+    conditional_block_range: Option<(u64, u64)>,
+
+    /// send every transaction a value drawn uniformly from this range, in wei, given as
+    /// MIN:MAX, on top of its calldata.  the signer has to hold the most the run could send.
+    #[arg(long, value_parser = value_range)]
+    value_range: Option<(u64, u64)>,
+
+    /// before the run, deploy a contract that copies and hashes whatever calldata it's sent
+    /// (from `tx_signer`) and use it as the receiver, so the transactions cost execution gas
+    /// on top of calldata.
+    #[arg(default_value = "false", long, num_args = 0)]
+    deploy_sink: bool,
+
+    /// in bundle mode, how many times to retry a bundle the relay failed to accept, or whose
+    /// inclusion check failed, before giving up on it.  other rpc calls aren't retried.
+    #[arg(default_value = "0", long)]
+    relay_timeout_retries: u32,
+
+    /// in bundle mode, what to do when a relay or rpc call in the submission loop fails:
+    /// `true` logs it and moves on to the next block, `false` aborts the run.  a bundle that
+    /// simply wasn't included is never an error.
+    #[arg(default_value_t = true, long, action = clap::ArgAction::Set)]
+    continue_on_error: bool,
+
+    /// wait before the first relay retry, doubling for each one after.
+    #[arg(default_value = "200", long)]
+    relay_retry_backoff_ms: u64,
+
+    /// in bundle mode, send bundles without a simulation block and timestamp, for bundles
+    /// too big for the relay to simulate that a builder might still include.
+    #[arg(default_value = "false", long, num_args = 0)]
+    no_simulation: bool,
+
+    /// in bundle mode, build the next bundle while waiting to see if the current one lands,
+    /// with the nonces it will need if it does, so it's ready as soon as the block arrives.
+    #[arg(default_value = "false", long, num_args = 0)]
+    prebuild: bool,
+
+    /// in bundle mode, submit to every relay in this json file instead of the default one,
+    /// each with its own bundle signer: `[{"url": "https://...", "signer": "<key>"}, ...]`.
+    /// the first relay's response decides whether a bundle counts as landed.
+    #[arg(long)]
+    relay_list_file: Option<PathBuf>,
+
+    /// in bundle mode, only share bundles with these builders (comma separated), through the
+    /// bundle's `builders` field.  left empty, the relay's default builder set gets them.
+    #[arg(long, value_delimiter = ',')]
+    target_builders: Vec<String>,
+
+    /// in bundle mode, send bundles as MEV-Share bundles (`mev_sendBundle`) instead, revealing
+    /// `mev_share_hints` to searchers.
+    #[arg(
+        default_value = "false",
+        long,
+        num_args = 0,
+        conflicts_with = "mem_pool"
+    )]
+    use_mev_share: bool,
+
+    /// in bundle mode, submit once per slot of this many seconds, `slot_offset_ms` after the
+    /// latest block's timestamp, instead of as soon as the block shows up.  a block seen twice
+    /// is only submitted for once.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    slot_seconds: Option<u64>,
+
+    /// with `--slot-seconds`, how far into the slot to submit.
+    #[arg(default_value = "2000", long, requires = "slot_seconds")]
+    slot_offset_ms: u64,
+
+    /// in bundle mode, wait for the block before block N and submit one bundle for block N
+    /// alone, instead of targeting every next block.  errors if block N is already past.
+    #[arg(
+        long,
+        conflicts_with_all = ["mem_pool", "keep_bundle_until_landed", "discover_min_tip"]
+    )]
+    target_slot: Option<u64>,
+
+    /// the MEV-Share node to send to with `--use-mev-share`.  defaults to the default relay.
+    #[arg(
+        long,
+        requires = "use_mev_share",
+        conflicts_with = "relay_list_file",
+        value_parser = http_provider
+    )]
+    mev_share_url: Option<String>,
+
+    /// with `--use-mev-share`, what searchers get to see about the bundle's transactions
+    /// (comma separated).
+    #[arg(default_value = "hash", long, value_delimiter = ',', value_enum)]
+    mev_share_hints: Vec<mev_share::Hint>,
+
+    /// in bundle mode, have the relay simulate bundles against the state at this block instead
+    /// of the current head.  the target block is still the next one.
+    #[arg(long, conflicts_with = "no_simulation")]
+    simulation_state_block: Option<u64>,
+
+    /// the gas limit of the block `--exclude-reverting` and the `simulate` subcommand simulate
+    /// bundles in, instead of the latest block's.  ignored otherwise.
+    #[arg(long, conflicts_with = "no_simulation")]
+    simulate_gas_limit: Option<u64>,
+
+    /// in bundle mode, resubmit the same signed bundle (same nonces) to each following block
+    /// until it lands, instead of signing a new one every block.
+    #[arg(
+        default_value = "false",
+        long,
+        num_args = 0,
+        conflicts_with = "discover_min_tip"
+    )]
+    keep_bundle_until_landed: bool,
+
+    /// in bundle mode, overwrite this file with each bundle as it's submitted (signed
+    /// transactions and target block), for the `replay` subcommand.
+    #[arg(long)]
+    dump_bundle: Option<PathBuf>,
+
+    /// in bundle mode, simulate the bundle on the relay before submitting it and drop any
+    /// transaction that reverts.  only useful when `receiver` is a real contract.
+    #[arg(default_value = "false", long, num_args = 0)]
+    exclude_reverting: bool,
+
+    /// in bundle mode, put a small ordinary transfer from `--normal-tx-signer` first in every
+    /// bundle, to check builders still include regular activity alongside the calldata.
+    #[arg(
+        default_value = "false",
+        long,
+        num_args = 0,
+        requires = "normal_tx_signer",
+        conflicts_with_all = ["mem_pool", "exclude_reverting"]
+    )]
+    include_normal_tx: bool,
+
+    /// the private key `--include-normal-tx` sends its transfers from, on its own nonces.
+    #[arg(long)]
+    #[serde(serialize_with = "redact")]
+    normal_tx_signer: Option<String>,
+
+    /// in bundle mode, list the calldata transactions in the bundle's `revertingTxHashes`, so
+    /// one that reverts (against a real contract `receiver`) doesn't invalidate the bundle.
+    #[arg(
+        default_value = "false",
+        long,
+        num_args = 0,
+        conflicts_with_all = ["mem_pool", "exclude_reverting"]
+    )]
+    allow_reverts: bool,
+
+    /// if using mempool, send every nonce twice with different gas prices to exercise the
+    /// node's replace-by-fee handling, and log which of the pair was included.
+    #[arg(default_value = "false", long, num_args = 0)]
+    duplicate_nonce: bool,
+
+    /// if using mempool, when the node answers "replacement transaction underpriced" (a
+    /// transaction is already pending at that nonce), re-sign it paying this many percent more
+    /// per gas and send it again.
+    #[arg(default_value = "10", long)]
+    replacement_bump_pct: u64,
+
+    /// show a live progress line (block, landed/attempted, tip, nonce) in bundle mode instead
+    /// of the scrolling logs.  ignored when stderr is not a terminal.
+    #[arg(default_value = "false", long, num_args = 0)]
+    tui: bool,
+
+    /// in bundle mode, every N blocks log a summary line and append a checkpoint record to
+    /// the attempts log.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    checkpoint_every: Option<u64>,
+
+    /// every N blocks, check the balance of every signer in use against `--low-balance-eth`.
+    /// in mempool mode that's before sending, between `--batch-size` waves and while the
+    /// receipts are awaited.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    balance_check_every: Option<u64>,
+
+    /// with `--balance-check-every`, warn when a signer holds less than this much ETH.
+    #[arg(default_value = "0.1", long, value_parser = eth_amount)]
+    #[serde(serialize_with = "as_eth")]
+    low_balance_eth: U256,
+
+    /// with `--balance-check-every`, stop submitting from a signer once it's below
+    /// `--low-balance-eth`, rather than only warning.
+    #[arg(default_value = "false", long, num_args = 0)]
+    stop_on_low_balance: bool,
+
+    /// sign the transactions a run would send (the mempool transactions, or the first bundle)
+    /// and print each one's hash and signed raw hex on stdout, one per line, instead of
+    /// submitting anything.
+    #[arg(default_value = "false", long, num_args = 0)]
+    dry_sign: bool,
+
+    /// when the run ends, or is interrupted with ctrl-c, replace every transaction that was
+    /// handed a nonce but isn't mined yet with a zero-value self-transfer at a higher gas
+    /// price, so the next run doesn't collide with them.
+    #[arg(
+        default_value = "false",
+        long,
+        num_args = 0,
+        conflicts_with = "dry_sign"
+    )]
+    cancel_on_exit: bool,
+
+    /// in mempool mode, on SIGUSR1 stop sending, replace every in-flight transaction like
+    /// `--cancel-on-exit` does, and wait to log which cancellations landed.  for unwinding a
+    /// batch sent at the wrong gas price.
+    #[arg(
+        default_value = "false",
+        long,
+        num_args = 0,
+        requires = "mem_pool",
+        conflicts_with = "dry_sign"
+    )]
+    cancel_on_signal: bool,
+
+    /// print the highest fill `--blocks` bundles can reach at the current gas limit and gas
+    /// price with the tx signer's balance, and how long it lasts at `--fill-pct`, then exit
+    /// without sending anything.
+    #[arg(
+        default_value = "false",
+        long,
+        num_args = 0,
+        conflicts_with = "mem_pool"
+    )]
+    estimate_only: bool,
+
+    /// in mempool mode, only count a transaction as landed once its block has this many
+    /// confirmations (1 = just included) and it's still there.  until then it's tentative.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    confirm_blocks: Option<u64>,
+
+    /// in bundle mode, re-check landed bundles this many blocks later and stop counting them
+    /// as landed if a reorg orphaned them.  the ones still short of it when the run ends are
+    /// rechecked once and reported as `tentative` in the summary.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    reorg_confirmations: Option<u64>,
+
+    /// stop submitting once the landed transactions have cost this much ETH in gas in total.
+    /// in mempool mode, where everything is sent at once, only as many transactions as could
+    /// cost at most this much are sent.
+    #[arg(long, value_parser = eth_amount)]
+    #[serde(serialize_with = "as_optional_eth")]
+    max_fee_cap_eth: Option<U256>,
+
+    /// in bundle mode, keep submitting for this many seconds (instead of until `--blocks`
+    /// have landed) and report the peak and sustained landed calldata throughput.
+    #[arg(long, value_parser = clap::value_parser!(u64).range(1..))]
+    measure_throughput: Option<u64>,
+
+    /// in bundle mode, fetch the whole block every landed bundle went into and report our
+    /// share of its calldata and gas, next to everyone else's transactions.
+    #[arg(default_value = "false", long, num_args = 0)]
+    analyze_blocks: bool,
+
+    /// in bundle mode, time each phase of every iteration (construction/signing, submission,
+    /// inclusion, waiting for the next block) and print the breakdown at the end.
+    #[arg(default_value = "false", long, num_args = 0)]
+    profile: bool,
+
+    /// in bundle mode, serve OpenMetrics on this address (e.g. `127.0.0.1:9464`): bundle
+    /// counters and an inclusion latency histogram whose buckets carry the block and tip of
+    /// their latest landing as exemplars.
+    #[arg(long)]
+    metrics_addr: Option<std::net::SocketAddr>,
+
+    /// tag for this run, added as `label` to every attempt and transaction record and to the
+    /// summary, e.g. `goerli-128kb-80pct`.
+    #[arg(default_value = "", long)]
+    label: String,
+
+    /// also append the end-of-run summary to the attempts log, as a record with
+    /// `"record_type": "summary"`.
+    #[arg(default_value = "false", long, num_args = 0)]
+    log_summary: bool,
+
+    /// append the hash, block and nonce of every landed transaction to this csv file, in both
+    /// modes.
+    #[arg(long)]
+    export_landed_txs: Option<PathBuf>,
+
+    /// in mempool mode, append the complete receipt of every landed transaction, logs and
+    /// all, to this file as a line of json.
+    #[arg(long)]
+    dump_receipts: Option<PathBuf>,
+
+    /// in bundle mode, append a csv row to this file for every block a bundle was sent for:
+    /// the running attempted and landed counts, the tip, the bundle's fill and the block's gas
+    /// utilization.
+    #[arg(long)]
+    timeseries_csv: Option<PathBuf>,
+
+    /// how to report the end-of-run summary.
+    #[arg(default_value = "table", long, value_enum)]
+    output_format: summary::OutputFormat,
+
+    /// file the summary row is appended to with `--output-format csv`.
+    #[arg(default_value = "stress-4844-summary.csv", long)]
+    summary_csv: String,
+
+    /// emit every bundle attempt, landing, eviction and reorg as a single-line json event on
+    /// stdout (with an `event_type` field).  human-readable logs move to stderr.
+    #[arg(default_value = "false", long, num_args = 0)]
+    events_stdout: bool,
+
+    /// gzip the attempts and mempool transaction logs, writing `.json.gz` files instead.
+    #[arg(default_value = "false", long, num_args = 0)]
+    compress_logs: bool,
+
+    /// print the fully resolved configuration (flags and preset applied, keys redacted) as
+    /// json and exit without running.
+    #[arg(default_value = "false", long, num_args = 0)]
+    #[serde(skip)]
+    dump_config: bool,
+
+    #[command(subcommand)]
+    #[serde(skip)]
+    command: Option<Command>,
+}
+
+/// `--dump-config` stand-in for private keys.  blank (unset) keys stay blank.
+fn redact<T: Redact + ?Sized, S: Serializer>(value: &T, serializer: S) -> Result<S::Ok, S::Error> {
+    value.redacted().serialize(serializer)
+}
+
+trait Redact {
+    fn redacted(&self) -> Value;
+}
+
+impl Redact for String {
+    fn redacted(&self) -> Value {
+        if self.is_empty() {
+            json!("")
+        } else {
+            json!("<redacted>")
+        }
+    }
+}
+
+impl Redact for Option<String> {
+    fn redacted(&self) -> Value {
+        match self {
+            Some(value) => value.redacted(),
+            None => Value::Null,
+        }
+    }
+}
+
+impl Redact for Vec<String> {
+    fn redacted(&self) -> Value {
+        Value::Array(self.iter().map(Redact::redacted).collect())
+    }
+}
+
+fn redact_headers<S: Serializer>(
+    headers: &[(HeaderName, HeaderValue)],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let headers: Vec<String> = headers
+        .iter()
+        .map(|(name, _)| format!("{name}: <redacted>"))
+        .collect();
+    headers.serialize(serializer)
+}
+
+fn as_eth<S: Serializer>(wei: &U256, serializer: S) -> Result<S::Ok, S::Error> {
+    as_optional_eth(&Some(*wei), serializer)
+}
+
+fn as_optional_eth<S: Serializer>(wei: &Option<U256>, serializer: S) -> Result<S::Ok, S::Error> {
+    wei.map(|wei| ethers::utils::format_units(wei, "ether").unwrap_or_default())
+        .serialize(serializer)
+}
+
+#[derive(Debug, clap::Subcommand)]
+enum Command {
+    /// check the rpc, the tx signer's balance and the relay (with a simulated no-op bundle),
+    /// reporting OK/FAIL for each, without submitting anything.
+    Selftest,
+    /// resubmit a bundle written by `--dump-bundle`, unchanged, for the next block.  its
+    /// transactions' nonces must still be current.
+    Replay {
+        /// the `--dump-bundle` file
+        file: PathBuf,
+    },
+    /// build a bundle as a run would and have the relay simulate it on the head block,
+    /// reporting total gas, coinbase payment and per-transaction success, without submitting
+    /// it or needing a reputable bundle signer.
+    Simulate,
+    /// an interactive prompt that adjusts fill, tip and chunk size and submits one bundle at a
+    /// time on demand, over one connection to the relay.
+    Repl,
+    /// follow an already submitted bundle through a range of blocks until it lands (and stays
+    /// landed for `confirmations` blocks), gets reorged out, or expires, without submitting
+    /// anything.
+    Watch {
+        /// the bundle's transaction hashes, first transaction first
+        #[arg(required_unless_present = "bundle_file")]
+        tx_hashes: Vec<TxHash>,
+        /// take the transactions from a `--dump-bundle` file instead
+        #[arg(long, conflicts_with = "tx_hashes")]
+        bundle_file: Option<PathBuf>,
+        /// a name for the bundle in the report, e.g. the hash the relay returned for it
+        #[arg(long)]
+        bundle_name: Option<String>,
+        /// first block to look in.  defaults to the dumped bundle's target block, or the head.
+        #[arg(long)]
+        from_block: Option<u64>,
+        /// the bundle expired if it's not in any block up to this one
+        #[arg(long)]
+        to_block: Option<u64>,
+        /// once it lands, how many blocks to wait before calling it landed for good
+        #[arg(default_value = "6", long, value_parser = clap::value_parser!(u64).range(1..))]
+        confirmations: u64,
+    },
+    /// sign a pool of mempool transactions, `--chunk-size` each and at consecutive nonces, and
+    /// write them to a file for `blast`, without submitting anything.
+    Prepare {
+        /// where to write the pool, a JSON list of hex raw transactions
+        file: PathBuf,
+        /// how many transactions to sign.  defaults to `--mempool-txs`.
+        #[arg(long)]
+        count: Option<usize>,
+        /// nonce of the first transaction.  defaults to the tx signer's current nonce.
+        #[arg(long)]
+        from_nonce: Option<u64>,
+    },
+    /// submit a `prepare`d pool to the rpc as fast as it takes them, `--send-concurrency` at a
+    /// time (in batches with `--rpc-batch-size`), and report the throughput.
+    Blast {
+        /// the `prepare` file
+        file: PathBuf,
+    },
+}
+
+/// how many blocks `watch` looks through when there's no `--to-block`
+const WATCH_BLOCKS: u64 = 25;
+
+/// the relay bundles are sent to  TODO: make configurable
+const RELAY_URL: &str = "https://relay-goerli.flashbots.net/";
+
+/// which state the starting nonce is read from
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+enum NonceBlock {
+    /// only confirmed transactions
+    Latest,
+    /// includes the node's view of pending transactions
+    Pending,
+}
+
+impl From<NonceBlock> for BlockNumber {
+    fn from(nonce_block: NonceBlock) -> Self {
+        match nonce_block {
+            NonceBlock::Latest => BlockNumber::Latest,
+            NonceBlock::Pending => BlockNumber::Pending,
+        }
+    }
 }
 
 fn http_provider(s: &str) -> Result<String, String> {
@@ -72,13 +819,126 @@ fn http_provider(s: &str) -> Result<String, String> {
     }
 }
 
+fn rpc_header(s: &str) -> Result<(HeaderName, HeaderValue), String> {
+    let (name, value) = s
+        .split_once(':')
+        .ok_or_else(|| format!("header must be \"Name: Value\": {s}"))?;
+    let name =
+        HeaderName::from_bytes(name.trim().as_bytes()).map_err(|e| format!("{e}: {name}"))?;
+    let mut value = HeaderValue::from_str(value.trim()).map_err(|e| format!("{e}: {name}"))?;
+    // keeps the value out of `Debug` output
+    value.set_sensitive(true);
+    Ok((name, value))
+}
+
+fn fill_percent(s: &str) -> Result<f64, String> {
+    let pct = s.parse::<f64>().map_err(|e| format!("{e}: {s}"))?;
+    if pct > 0.0 && pct <= bundle_builder::MAX_FILL_PCT {
+        Ok(pct)
+    } else {
+        Err(format!(
+            "fill percentage must be in (0, {}]: {s}",
+            bundle_builder::MAX_FILL_PCT
+        ))
+    }
+}
+
+fn gas_multiplier(s: &str) -> Result<f64, String> {
+    let multiplier = s.parse::<f64>().map_err(|e| format!("{e}: {s}"))?;
+    if multiplier.is_finite() && multiplier >= 1.0 {
+        Ok(multiplier)
+    } else {
+        Err(format!("gas multiplier must be at least 1.0: {s}"))
+    }
+}
+
+/// parse a size with a `b`, `kb` or `mb` suffix (1024-based) into bytes.  bare numbers are
+/// kbytes, the unit `--chunk-size` used to have implicitly.
+fn data_size(s: &str) -> Result<usize, String> {
+    let lower = s.trim().to_ascii_lowercase();
+    let (digits, unit) = match lower.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => lower.split_at(i),
+        None => (lower.as_str(), "kb"),
+    };
+    let multiplier = match unit.trim() {
+        "b" => 1,
+        "k" | "kb" => bundle_builder::KB,
+        "m" | "mb" => bundle_builder::KB * bundle_builder::KB,
+        _ => {
+            return Err(format!(
+                "unknown size unit {unit:?}, expected b, kb or mb: {s}"
+            ))
+        }
+    };
+    let size = digits.parse::<usize>().map_err(|e| format!("{e}: {s}"))?;
+    size.checked_mul(multiplier)
+        .ok_or_else(|| format!("size is too large: {s}"))
+}
+
+fn eth_amount(s: &str) -> Result<U256, String> {
+    ethers::utils::parse_ether(s).map_err(|e| format!("{e}: {s}"))
+}
+
+fn tip_percent(s: &str) -> Result<f64, String> {
+    let pct = s.parse::<f64>().map_err(|e| format!("{e}: {s}"))?;
+    if pct.is_finite() && pct >= 0.0 {
+        Ok(pct)
+    } else {
+        Err(format!("tip percentage must be a non-negative number: {s}"))
+    }
+}
+
+fn jitter_percent(s: &str) -> Result<f64, String> {
+    let pct = s.parse::<f64>().map_err(|e| format!("{e}: {s}"))?;
+    if (0.0..100.0).contains(&pct) {
+        Ok(pct)
+    } else {
+        Err(format!("gas price jitter must be in [0, 100): {s}"))
+    }
+}
+
+fn percentile(s: &str) -> Result<f64, String> {
+    let percentile = s.parse::<f64>().map_err(|e| format!("{e}: {s}"))?;
+    if (0.0..=100.0).contains(&percentile) {
+        Ok(percentile)
+    } else {
+        Err(format!("percentile must be in [0, 100]: {s}"))
+    }
+}
+
+fn block_range(s: &str) -> Result<(u64, u64), String> {
+    let (min, max) = s
+        .split_once(':')
+        .ok_or_else(|| format!("block range must be MIN:MAX: {s}"))?;
+    let min = min.parse::<u64>().map_err(|e| format!("{e}: {min}"))?;
+    let max = max.parse::<u64>().map_err(|e| format!("{e}: {max}"))?;
+    if min > max {
+        return Err(format!("block range min is above max: {s}"));
+    }
+    Ok((min, max))
+}
+
+fn value_range(s: &str) -> Result<(u64, u64), String> {
+    let (min, max) = s
+        .split_once(':')
+        .ok_or_else(|| format!("value range must be MIN:MAX: {s}"))?;
+    let min = min.parse::<u64>().map_err(|e| format!("{e}: {min}"))?;
+    let max = max.parse::<u64>().map_err(|e| format!("{e}: {max}"))?;
+    if min > max {
+        return Err(format!("value range min is above max: {s}"));
+    }
+    Ok((min, max))
+}
+
 /// log mev-boost bundle landing attempts, and whether they succeeded or not
 fn get_attempt_json(
     chunk_size: usize,
     tip_wei: u64,
-    fill_pct: u8,
+    fill_pct: f64,
     success: bool,
     block_no: U64,
+    latency: AttemptLatency,
+    estimated_inclusion_prob: Option<f64>,
 ) -> Value {
     let entry = json!({
             "tip_wei": tip_wei,
@@ -87,27 +947,154 @@ fn get_attempt_json(
             "time": Utc::now().to_string(),
             "chunk_size": chunk_size,
             "block_no": block_no,
+            "relay_latency_ms": latency.relay.as_millis() as u64,
+            "inclusion_latency_ms": latency.inclusion.map(|d| d.as_millis() as u64),
+            "estimated_inclusion_prob": estimated_inclusion_prob,
+            "label": label(),
     });
-    return entry;
+    entry
+}
+
+const ATTEMPTS_LOG: &str = "stress-4844-attempts.json";
+const MEMPOOL_TXNS_LOG: &str = "stress-4844-mempool-txns.json";
+
+/// set once at startup by `--compress-logs`
+static COMPRESS_LOGS: AtomicBool = AtomicBool::new(false);
+
+/// set once at startup by `--label`, and added to every attempt and transaction record
+static LABEL: OnceLock<String> = OnceLock::new();
+
+fn label() -> &'static str {
+    LABEL.get().map(String::as_str).unwrap_or_default()
 }
 
-fn log_attempt(chunk_size: usize, tip_wei: u64, fill_pct: u8, success: bool, block_no: U64) {
-    let _entry = get_attempt_json(chunk_size, tip_wei, fill_pct, success, block_no);
-    let mut file = OpenOptions::new()
+/// append one json record to an NDJSON log file.  with `--compress-logs` it goes to
+/// `<path>.gz` instead, as a gzip member of its own; `zcat` reads the members back to back.
+fn append_record(path: &str, entry: &Value) {
+    let compress = COMPRESS_LOGS.load(Ordering::Relaxed);
+    let path = if compress {
+        format!("{path}.gz")
+    } else {
+        path.to_string()
+    };
+    let file = OpenOptions::new()
         .create(true)
         .append(true)
-        .open("stress-4844-attempts.json")
+        .open(path)
         .unwrap();
 
-    let _res = file.write_all(b"\n");
-    let res = serde_json::to_writer(file, &_entry);
+    let res = if compress {
+        let mut encoder = GzEncoder::new(file, Compression::default());
+        write_record(&mut encoder, entry).and_then(|_| encoder.finish().map(|_| ()))
+    } else {
+        write_record(file, entry)
+    };
 
-    match res {
-        Err(e) => eprintln!("Couldn't write to file: {}", e),
-        Ok(_) => {
-            return;
-        }
+    if let Err(e) = res {
+        eprintln!("Couldn't write to file: {}", e);
+    }
+}
+
+fn write_record(mut writer: impl Write, entry: &Value) -> std::io::Result<()> {
+    let _res = writer.write_all(b"\n");
+    Ok(serde_json::to_writer(writer, entry)?)
+}
+
+/// how long one bundle attempt took at the relay and on chain
+#[derive(Debug, Clone, Copy)]
+struct AttemptLatency {
+    /// from calling `send_bundle` until the relay acknowledged it
+    relay: Duration,
+    /// from submission until the target block was mined with the bundle in it
+    inclusion: Option<Duration>,
+}
+
+fn log_attempt(
+    chunk_size: usize,
+    tip_wei: u64,
+    fill_pct: f64,
+    success: bool,
+    block_no: U64,
+    latency: AttemptLatency,
+    estimated_inclusion_prob: Option<f64>,
+) {
+    let _entry = get_attempt_json(
+        chunk_size,
+        tip_wei,
+        fill_pct,
+        success,
+        block_no,
+        latency,
+        estimated_inclusion_prob,
+    );
+    events::emit("bundle_attempt", &_entry);
+    if success {
+        events::emit("bundle_landed", &_entry);
     }
+    append_record(ATTEMPTS_LOG, &_entry);
+}
+
+/// a mempool transaction that disappeared before being mined
+fn log_eviction(tx_hash: TxHash, nonce: U256, gas_price: U256) {
+    let entry = json!({
+            "record_type": "evicted",
+            "tx_hash": tx_hash,
+            "nonce": nonce,
+            "gas_price": gas_price,
+            "time": Utc::now().to_string(),
+    });
+    events::emit("eviction", &entry);
+    append_record(MEMPOOL_TXNS_LOG, &entry);
+}
+
+/// a previously landed bundle that got reorged out of the canonical chain
+fn log_reorg(landed_block: U64, tx_hash: TxHash, block_no: U64) {
+    let entry = json!({
+            "record_type": "reorg",
+            "landed_block": landed_block,
+            "tx_hash": tx_hash,
+            "block_no": block_no,
+            "time": Utc::now().to_string(),
+    });
+    events::emit("reorg", &entry);
+    append_record(ATTEMPTS_LOG, &entry);
+}
+
+/// the head block number going backwards, from `highest` to `block_no`
+fn log_head_reorg(highest: U64, block_no: U64) {
+    let entry = json!({
+            "record_type": "head_reorg",
+            "highest_block": highest,
+            "block_no": block_no,
+            "time": Utc::now().to_string(),
+    });
+    events::emit("head_reorg", &entry);
+    append_record(ATTEMPTS_LOG, &entry);
+}
+
+/// periodic snapshot of a bundle run, written alongside the attempts
+fn log_checkpoint(blocks: u64, landed: usize, attempted: usize, elapsed: Duration, tip_wei: u64) {
+    let entry = json!({
+            "record_type": "checkpoint",
+            "blocks": blocks,
+            "landed": landed,
+            "attempted": attempted,
+            "success_rate": progress::success_rate(landed, attempted),
+            "elapsed_secs": elapsed.as_secs(),
+            "tip_wei": tip_wei,
+            "time": Utc::now().to_string(),
+    });
+    events::emit("checkpoint", &entry);
+    append_record(ATTEMPTS_LOG, &entry);
+}
+
+/// the end-of-run summary, as the last record of the attempts log
+fn log_summary(summary: &summary::Summary) -> eyre::Result<()> {
+    let mut entry = serde_json::to_value(summary)?;
+    entry["record_type"] = json!("summary");
+    entry["time"] = json!(Utc::now().to_string());
+    append_record(ATTEMPTS_LOG, &entry);
+    Ok(())
 }
 
 /// log individual mempool transactions as they land
@@ -119,298 +1106,2523 @@ fn get_txn_json(txn: TransactionReceipt) -> Value {
             "time": Utc::now().to_string(),
             "block_no": txn.block_number.unwrap(),
             "status": txn.status.unwrap(),
+            "label": label(),
     });
-    return entry;
+    entry
+}
+
+fn log_txn(txn: TransactionReceipt) {
+    let _entry = get_txn_json(txn);
+    events::emit("tx_landed", &_entry);
+    append_record(MEMPOOL_TXNS_LOG, &_entry);
+}
+
+/// `--export-landed-txs`: one `tx_hash,block,nonce` row, with a header if the file is new
+fn export_landed_tx(path: &Path, tx_hash: TxHash, block: U64, nonce: U256) -> eyre::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    if file.metadata()?.len() == 0 {
+        writeln!(file, "tx_hash,block,nonce")?;
+    }
+    writeln!(file, "{tx_hash:?},{block},{nonce}")?;
+    Ok(())
+}
+
+/// `--timeseries-csv`: one row for a bundle sent at `block`, with a header if the file is new
+fn append_timeseries_row(
+    path: &Path,
+    block: &Block<H256>,
+    attempted: usize,
+    landed: usize,
+    tip_wei: u64,
+    fill_pct: f64,
+) -> eyre::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    if file.metadata()?.len() == 0 {
+        writeln!(
+            file,
+            "block_number,timestamp,attempted,landed,tip_wei,achieved_fill_pct,gas_utilization"
+        )?;
+    }
+    let utilization = if block.gas_limit.is_zero() {
+        0.0
+    } else {
+        block.gas_used.as_u64() as f64 / block.gas_limit.as_u64() as f64
+    };
+    writeln!(
+        file,
+        "{},{},{attempted},{landed},{tip_wei},{fill_pct:.2},{utilization:.4}",
+        block.number.unwrap_or_default(),
+        block.timestamp
+    )?;
+    Ok(())
+}
+
+/// `--dump-receipts`: the whole receipt as one json line
+fn dump_receipt(path: &Path, receipt: &TransactionReceipt) -> eyre::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(receipt)?)?;
+    Ok(())
+}
+
+/// with `--value-range`, fail unless `address` holds enough for `txs` transactions that all
+/// send the maximum value
+async fn check_value_balance<M: Middleware>(
+    provider: &M,
+    address: Address,
+    txs: usize,
+    opts: &Opts,
+) -> eyre::Result<()>
+where
+    M::Error: 'static,
+{
+    let Some((_, max)) = opts.value_range else {
+        return Ok(());
+    };
+    let needed = U256::from(max) * U256::from(txs);
+    let balance = provider.get_balance(address, None).await?;
+    if balance < needed {
+        eyre::bail!(
+            "{address:?} has {} ETH, but {txs} transactions could send {} ETH of --value-range",
+            ethers::utils::format_units(balance, "ether")?,
+            ethers::utils::format_units(needed, "ether")?
+        );
+    }
+    Ok(())
+}
+
+/// what a mined transaction paid for gas
+fn receipt_cost(receipt: &TransactionReceipt) -> U256 {
+    receipt.gas_used.unwrap_or_default() * receipt.effective_gas_price.unwrap_or_default()
+}
+
+fn wei_to_eth(wei: U256) -> eyre::Result<f64> {
+    Ok(ethers::utils::format_units(wei, "ether")?.parse::<f64>()?)
+}
+
+/// check `address` against `--low-balance-eth`, warning when it's below.  true if it isn't.
+async fn balance_ok<M: Middleware>(
+    provider: &M,
+    address: Address,
+    opts: &Opts,
+) -> eyre::Result<bool>
+where
+    M::Error: 'static,
+{
+    let balance = provider.get_balance(address, None).await?;
+    let eth = ethers::core::utils::format_units(balance, "eth")?;
+    if balance < opts.low_balance_eth {
+        tracing::warn!(
+            "low balance: {address:?} has {eth} ETH, below --low-balance-eth {}",
+            ethers::core::utils::format_units(opts.low_balance_eth, "eth")?
+        );
+        return Ok(false);
+    }
+    tracing::debug!("balance of {address:?}: {eth} ETH");
+    Ok(true)
+}
+
+/// `balance_ok` for every one of `addresses`, false if any of them is low
+async fn balances_ok<M: Middleware>(
+    provider: &M,
+    addresses: &[Address],
+    opts: &Opts,
+) -> eyre::Result<bool>
+where
+    M::Error: 'static,
+{
+    let mut ok = true;
+    for address in addresses {
+        ok &= balance_ok(provider, *address, opts).await?;
+    }
+    Ok(ok)
+}
+
+/// how often `watch_balances` looks for a new block
+const BALANCE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// with `--balance-check-every N`, check `addresses` every N blocks for as long as it's
+/// polled.  only warns, since it runs once everything was sent.  never resolves.
+async fn watch_balances<M: Middleware>(provider: &M, addresses: &[Address], opts: &Opts)
+where
+    M::Error: 'static,
+{
+    let Some(every) = opts.balance_check_every else {
+        return std::future::pending().await;
+    };
+    let mut checked_at: Option<U64> = None;
+    loop {
+        tokio::time::sleep(BALANCE_POLL_INTERVAL).await;
+        let block = match provider.get_block_number().await {
+            Ok(block) => block,
+            Err(err) => {
+                tracing::debug!("{err}, checking balances on the next poll");
+                continue;
+            }
+        };
+        match checked_at {
+            // everything was checked just before sending
+            None => checked_at = Some(block),
+            Some(at) if block >= at + every => {
+                checked_at = Some(block);
+                if let Err(err) = balances_ok(provider, addresses, opts).await {
+                    tracing::debug!("{err}, checking balances again in {every} blocks");
+                }
+            }
+            Some(_) => {}
+        }
+    }
+}
+
+/// the `--bundle-signer` key, or a freshly generated one when it's left blank
+fn parse_bundle_signer(opts: &Opts) -> eyre::Result<LocalWallet> {
+    if opts.bundle_signer.is_empty() {
+        // no reputation to lose on a first run, so any fresh key will do
+        let bundle_signer = LocalWallet::new(&mut rand::thread_rng());
+        tracing::info!(
+            "no --bundle-signer given, generated {:?} for this run",
+            bundle_signer.address()
+        );
+        Ok(bundle_signer)
+    } else {
+        Ok(opts
+            .bundle_signer
+            .strip_prefix("0x")
+            .unwrap_or(&opts.bundle_signer)
+            .parse::<LocalWallet>()?)
+    }
+}
+
+/// Creation code for the `--deploy-sink` contract.  The 12 byte constructor returns the
+/// runtime code after it, which for any call does:
+///
+/// CALLDATASIZE PUSH1 0 PUSH1 0 CALLDATACOPY   // copy all calldata to memory
+/// CALLDATASIZE PUSH1 0 SHA3 POP STOP          // and hash it
+const SINK_CREATION_CODE: &str = "0x600c600c600039600c6000f3366000600037366000205000";
+
+/// deploy the calldata sink from `signer` and wait for it to be mined
+async fn deploy_sink(
+    provider: Arc<Provider<Http>>,
+    signer: LocalWallet,
+    chain_id: u64,
+) -> eyre::Result<Address> {
+    let deployer = SignerMiddleware::new(provider, signer);
+    let tx = TransactionRequest::new()
+        .chain_id(chain_id)
+        .data(SINK_CREATION_CODE.parse::<Bytes>()?);
+    let receipt = deployer
+        .send_transaction(tx, None)
+        .await?
+        .await?
+        .ok_or_else(|| eyre::eyre!("calldata sink deployment was dropped"))?;
+    receipt
+        .contract_address
+        .ok_or_else(|| eyre::eyre!("calldata sink deployment created no contract"))
+}
+
+/// Address of the following contract to allow for easy coinbase payments on Goerli.
+///
+/// contract CoinbasePayer {
+///     receive() payable external {
+///         payable(address(block.coinbase)).transfer(msg.value);
+///     }
+/// }
+// const COINBASE_PAYER_ADDR: &str = "0x060d6635bb76c71871f97C12f10Fa20BD8e87eC0";
+
+#[tokio::main]
+async fn main() -> eyre::Result<()> {
+    let matches = Opts::command().get_matches();
+    let mut opts = Opts::from_arg_matches(&matches)?;
+    preset::apply(&mut opts, &matches);
+    if opts.dump_config {
+        println!("{}", serde_json::to_string_pretty(&opts)?);
+        return Ok(());
+    }
+
+    let rpc_url = opts.rpc_url.as_str();
+
+    let use_mempool = opts.mem_pool;
+
+    let mut derived = match &opts.mnemonic {
+        Some(mnemonic) => wallets::derive(mnemonic, opts.wallet_index.clone())?,
+        None => Vec::new(),
+    };
+    let signer = if opts.tx_signer.is_empty() {
+        derived.remove(0)
+    } else {
+        let tx_signer = opts.tx_signer.strip_prefix("0x").unwrap_or(&opts.tx_signer);
+        tx_signer.parse::<LocalWallet>()?
+    };
+
+    let interval = Duration::from_secs(1);
+
+    // with the progress line active, only let warnings through so they don't clobber it
+    let show_progress = opts.tui && !use_mempool && progress::is_tty();
+    let filter = if show_progress {
+        "stress4844=warn"
+    } else {
+        "stress4844=trace"
+    };
+    if opts.events_stdout {
+        events::enable();
+    }
+    if opts.compress_logs {
+        COMPRESS_LOGS.store(true, Ordering::Relaxed);
+    }
+    let _ = LABEL.set(opts.label.clone());
+    // stdout is reserved for the event stream once it's turned on
+    let log_layer = if opts.events_stdout {
+        tracing_subscriber::fmt::layer()
+            .with_writer(std::io::stderr)
+            .boxed()
+    } else {
+        tracing_subscriber::fmt::layer().boxed()
+    };
+    tracing_subscriber::registry()
+        .with(log_layer)
+        .with(EnvFilter::new(filter))
+        .init();
+    summary::install_panic_hook(opts.output_format, opts.summary_csv.clone());
+
+    let bare_chunk_size = matches
+        .get_raw("chunk_size")
+        .and_then(|mut raw| raw.next())
+        .and_then(|raw| raw.to_str())
+        .filter(|raw| raw.trim().chars().all(|c| c.is_ascii_digit()));
+    if let (Some(raw), Some(ValueSource::CommandLine)) =
+        (bare_chunk_size, matches.value_source("chunk_size"))
+    {
+        tracing::warn!("--chunk-size {raw} without a unit is read as {raw}kb; bare numbers are deprecated, pass e.g. {raw}kb");
+    }
+
+    // the "usual" rpc provider, no flashbots mev-boost middleware
+    let provider: Arc<Provider<Http>> = if opts.rpc_header.is_empty() {
+        Arc::new(Provider::<Http>::try_from(rpc_url)?.interval(interval))
+    } else {
+        let names: Vec<_> = opts
+            .rpc_header
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect();
+        tracing::info!("sending rpc headers {} (values redacted)", names.join(", "));
+        let headers: HeaderMap = opts.rpc_header.iter().cloned().collect();
+        let client = reqwest::Client::builder()
+            .default_headers(headers)
+            .build()?;
+        let http = Http::new_with_client(Url::parse(rpc_url)?, client);
+        Arc::new(Provider::new(http).interval(interval))
+    };
+
+    match &opts.command {
+        Some(Command::Selftest) => {
+            let bundle_signer = parse_bundle_signer(&opts)?;
+            return selftest::run(provider, signer, bundle_signer, Url::parse(RELAY_URL)?).await;
+        }
+        Some(Command::Replay { file }) => {
+            let bundle_signer = parse_bundle_signer(&opts)?;
+            return replay::run(provider, bundle_signer, Url::parse(RELAY_URL)?, file).await;
+        }
+        Some(Command::Watch {
+            tx_hashes,
+            bundle_file,
+            bundle_name,
+            from_block,
+            to_block,
+            confirmations,
+        }) => {
+            let (dumped_block, transactions) = match bundle_file {
+                Some(path) => {
+                    let dumped = replay::DumpedBundle::read(path)?;
+                    (dumped.block, dumped.tx_hashes())
+                }
+                None => (None, tx_hashes.clone()),
+            };
+            if transactions.is_empty() {
+                eyre::bail!("there are no transactions to watch");
+            }
+            let from = match (from_block, dumped_block) {
+                (Some(block), _) => U64::from(*block),
+                (None, Some(block)) => block,
+                (None, None) => provider.get_block_number().await?,
+            };
+            let to = U64::from(to_block.unwrap_or(from.as_u64() + WATCH_BLOCKS));
+            return watch::run(
+                provider,
+                bundle_name.clone(),
+                transactions,
+                from,
+                to,
+                *confirmations,
+            )
+            .await;
+        }
+        Some(Command::Blast { file }) => {
+            let transactions = pool::read(file)?;
+            let batcher = match opts.rpc_batch_size {
+                Some(batch_size) => {
+                    let headers = opts.rpc_header.iter().cloned().collect();
+                    let batcher = rpc_batch::BatchSender::new(Url::parse(&opts.rpc_url)?, headers)?;
+                    Some((batcher, batch_size as usize))
+                }
+                None => None,
+            };
+            return pool::blast(
+                provider,
+                transactions,
+                opts.send_concurrency as usize,
+                batcher,
+            )
+            .await;
+        }
+        Some(Command::Simulate | Command::Repl | Command::Prepare { .. }) | None => {}
+    }
+
+    let chain_id = provider.get_chainid().await?.as_u64();
+
+    let address = signer.address();
+    let balance = provider.get_balance(address, None).await?;
+
+    tracing::info!(
+        "starting benchmark from {:?} (balance: {} ETH)",
+        address,
+        ethers::core::utils::format_units(balance, "eth")?,
+    );
+
+    let simulate_only = matches!(opts.command, Some(Command::Simulate));
+    let preparing = matches!(opts.command, Some(Command::Prepare { .. }));
+    let nothing_submitted = simulate_only || preparing || opts.dry_sign || opts.estimate_only;
+    if opts.deploy_sink && nothing_submitted {
+        tracing::warn!("ignoring --deploy-sink, nothing is submitted");
+    }
+    let sink = if opts.deploy_sink && !nothing_submitted {
+        let sink = deploy_sink(provider.clone(), signer.clone(), chain_id).await?;
+        tracing::info!("deployed calldata sink at {sink:?}");
+        Some(sink)
+    } else {
+        None
+    };
+
+    let nonces = nonce::NonceManager::default();
+    let nonce = nonces
+        .resync(address, &provider, opts.nonce_block.into())
+        .await?;
+    tracing::debug!("current nonce: {nonce}, use_mempool = {use_mempool}");
+    let normal_tx_signer = match &opts.normal_tx_signer {
+        Some(key) if opts.include_normal_tx => {
+            let wallet = key
+                .strip_prefix("0x")
+                .unwrap_or(key)
+                .parse::<LocalWallet>()?;
+            let normal_nonce = nonces
+                .resync(wallet.address(), &provider, opts.nonce_block.into())
+                .await?;
+            tracing::info!(
+                "prepending a transfer from {:?} (nonce {normal_nonce}) to every bundle",
+                wallet.address()
+            );
+            Some(wallet)
+        }
+        _ => None,
+    };
+    // TODO: Do we want this to be different per transaction?
+    let receiver: Address = match sink {
+        Some(sink) => sink,
+        None => "0x4844AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA".parse()?,
+    };
+
+    let block = provider
+        .get_block(BlockNumber::Latest)
+        .await?
+        .expect("could not get latest block");
+
+    if let Some(min_gas_limit) = opts.min_block_gas_limit {
+        if block.gas_limit < U256::from(min_gas_limit) {
+            eyre::bail!(
+                "block gas limit {} is below --min-block-gas-limit {min_gas_limit}",
+                block.gas_limit
+            );
+        }
+    }
+
+    let payload: Arc<dyn payload::PayloadGenerator> = match &opts.payload_pattern_file {
+        Some(path) => {
+            let pattern = pattern::Pattern::load(path)?;
+            tracing::info!(
+                "tiling a {} byte payload pattern over the calldata",
+                pattern.len()
+            );
+            Arc::new(pattern)
+        }
+        None => opts.data_fill.generator(),
+    };
+    let gas_oracle = opts.gas_oracle.oracle(
+        provider.clone(),
+        opts.blocknative_api_key.as_deref(),
+        opts.blocknative_confidence,
+        opts.fixed_gas_price_wei,
+    )?;
+    tracing::debug!("gas prices from {:?}", opts.gas_oracle);
+    let mut params = bundle_builder::TxParams {
+        chain_id,
+        address,
+        receiver,
+        gas_multiplier: opts.gas_multiplier,
+        gas_cap: block.gas_limit,
+        fork: bundle_builder::Fork::detect(&provider).await?,
+        assume_gas: opts.assume_gas.filter(|_| !use_mempool).map(U256::from),
+        priority_fee_percentile: opts.priority_fee_percentile,
+        gas_price_cap: opts.gas_price_cap_wei.map(U256::from),
+        tip_pct_over_base: opts.tip_pct_over_base,
+        payload,
+        bundle_order: opts.bundle_order,
+        max_txs_per_bundle: opts.max_txs_per_bundle,
+        size_distribution: opts.size_distribution.clone(),
+        replay_distribution: opts.replay_distribution.clone(),
+        // set-code transactions are priced like type-2 ones
+        eip1559: opts.eip1559 || opts.tx_type.is_some(),
+        value_range: opts.value_range,
+        gas_price_jitter_pct: opts.gas_price_jitter_pct,
+        normal_tx_signer,
+        allow_reverts: opts.allow_reverts,
+        gas_price_snapshot: None,
+        gas_oracle,
+        set_code: opts
+            .authorization_list
+            .as_ref()
+            .map(|list| bundle_builder::SetCode {
+                signer: signer.clone(),
+                authorizations: list.authorizations.clone(),
+            }),
+    };
+    tracing::info!(
+        "--fill-pct is a percentage of {} bytes, the calldata a {} gas block holds at {:?}'s {} gas per byte (at most 2MB)",
+        bundle_builder::block_data_bytes(block.gas_limit, params.fork),
+        block.gas_limit,
+        params.fork,
+        params.fork.calldata_gas_per_byte()
+    );
+    if params.set_code.is_some() && params.fork != bundle_builder::Fork::Prague {
+        eyre::bail!(
+            "--tx-type 7702 needs EIP-7702, which isn't active before Prague; the chain is at {:?}",
+            params.fork
+        );
+    }
+    if opts.fixed_gas_snapshot {
+        let snapshot = bundle_builder::network_gas_price(&params, &provider).await?;
+        tracing::info!(
+            "--fixed-gas-snapshot: building every gas price on {snapshot} wei for the whole run"
+        );
+        params.gas_price_snapshot = Some(snapshot);
+    }
+    tracing::debug!("detected fork: {:?}", params.fork);
+
+    let mut extra_signers = opts
+        .extra_tx_signer
+        .iter()
+        .map(|key| key.strip_prefix("0x").unwrap_or(key).parse::<LocalWallet>())
+        .collect::<Result<Vec<_>, _>>()?;
+    extra_signers.extend(derived);
+    if opts.auto_chunk && use_mempool {
+        tracing::warn!(
+            "--auto-chunk only applies to bundles, keeping --chunk-size {}",
+            opts.chunk_size
+        );
+    } else if opts.auto_chunk {
+        let (chunk_size, fill_pct) = bundle_builder::tune_chunk_size(
+            &params,
+            &provider,
+            block.gas_limit,
+            opts.fill_pct,
+            opts.max_bundle_bytes,
+        )
+        .await?;
+        tracing::info!(
+            "--auto-chunk picked a chunk size of {} bytes, filling {fill_pct}% of the block (asked for {}%)",
+            chunk_size,
+            opts.fill_pct
+        );
+        opts.chunk_size = chunk_size;
+        opts.fill_pct = fill_pct;
+    }
+
+    if !use_mempool && !extra_signers.is_empty() {
+        tracing::warn!(
+            "--extra-tx-signer only applies to --mem-pool, bundles are signed by --tx-signer alone"
+        );
+    }
+
+    if opts.estimate_only {
+        return estimate::run(&params, &provider, block.gas_limit, balance, &opts).await;
+    }
+
+    if let Some(Command::Prepare {
+        file,
+        count,
+        from_nonce,
+    }) = &opts.command
+    {
+        let client = SignerMiddleware::new_with_provider_chain(provider.clone(), signer).await?;
+        let gas_price = match params.gas_price_snapshot {
+            Some(snapshot) => snapshot,
+            None => bundle_builder::network_gas_price(&params, &provider).await?,
+        };
+        return pool::prepare(
+            &params,
+            &client,
+            count.unwrap_or(opts.mempool_txs),
+            from_nonce.map(U256::from).unwrap_or(nonce),
+            opts.chunk_size,
+            gas_price,
+            file,
+        )
+        .await;
+    }
+
+    if simulate_only {
+        let client = SignerMiddleware::new_with_provider_chain(provider.clone(), signer).await?;
+        let bundle = next_bundle(
+            &params,
+            &client,
+            block.gas_limit,
+            nonce,
+            params
+                .normal_tx_signer
+                .as_ref()
+                .map(|signer| nonces.current(signer.address())),
+            opts.tip_wei,
+            &opts,
+        )
+        .await?;
+        check_simulation_state_block(&provider, &opts).await?;
+        let head = block.number.unwrap_or_default();
+        let state = opts.simulation_state_block.map(U64::from).unwrap_or(head);
+        let bundle_signer = parse_bundle_signer(&opts)?;
+        return simulate::run(
+            provider,
+            bundle,
+            bundle_signer,
+            Url::parse(RELAY_URL)?,
+            head + 1,
+            state,
+            opts.simulate_gas_limit
+                .map(U256::from)
+                .unwrap_or(block.gas_limit),
+        )
+        .await;
+    }
+
+    if let Some(Command::Repl) = opts.command {
+        let bundle_signer = parse_bundle_signer(&opts)?;
+        let relay_url = Url::parse(RELAY_URL)?;
+        return repl::run(provider, signer, bundle_signer, relay_url, params, &opts).await;
+    }
+
+    // every key that signs transactions, for `--cancel-on-exit` and `--cancel-on-signal`
+    let cancel_signers: Vec<LocalWallet> = std::iter::once(signer.clone())
+        .chain(extra_signers.iter().cloned())
+        .collect();
+    let cancel_provider = provider.clone();
+    let cancel_on_exit = opts.cancel_on_exit;
+    let cancel_on_signal = opts.cancel_on_signal;
+    let run = async {
+        Ok::<_, eyre::Report>(if use_mempool {
+            // Sign transactions with a private key
+            let provider = SignerMiddleware::new(provider, signer);
+            submit_txns(provider, extra_signers, &params, &nonces, &opts).await?
+        } else {
+            let relays = match &opts.relay_list_file {
+                Some(path) => relays::load(path)?,
+                None => vec![relays::RelayEntry {
+                    url: Url::parse(opts.mev_share_url.as_deref().unwrap_or(RELAY_URL))?,
+                    signer: parse_bundle_signer(&opts)?,
+                }],
+            };
+            for relay in &relays {
+                tracing::info!("relay {} signs as {:?}", relay.url, relay.signer.address());
+            }
+            if relays.iter().any(|relay| relay.signer.address() == address) {
+                // the bundle signer only signs relay requests, but anything else sent from this
+                // key (e.g. to build up relay reputation) moves the same nonce we're using
+                tracing::info!(
+                    "--tx-signer and --bundle-signer are the same key {address:?}: the relay scores reputation on the address that also sends the stress transactions, and any other transaction it signs shares their nonce"
+                );
+                if opts.nonce_block == NonceBlock::Latest {
+                    tracing::warn!("resyncing the shared key's nonce from the pending block instead of --nonce-block latest");
+                    opts.nonce_block = NonceBlock::Pending;
+                    nonces
+                        .resync(address, &provider, BlockNumber::Pending)
+                        .await?;
+                }
+            }
+
+            submit_bundles(
+                provider,
+                &params,
+                &nonces,
+                block,
+                signer,
+                relays,
+                show_progress,
+                &opts,
+            )
+            .await?
+        })
+    };
+    let mut unwinding = false;
+    let outcome = tokio::select! {
+        outcome = run => outcome.map(Some),
+        _ = tokio::signal::ctrl_c(), if cancel_on_exit => {
+            tracing::warn!("interrupted, cancelling outstanding transactions");
+            Ok(None)
+        }
+        signal = cancel_signal(), if cancel_on_signal => {
+            signal?;
+            tracing::warn!("got SIGUSR1, stopping and cancelling every in-flight transaction");
+            unwinding = true;
+            Ok(None)
+        }
+    };
+    if cancel_on_exit || unwinding {
+        let sent = cancel_outstanding(&cancel_provider, cancel_signers, &nonces, chain_id).await?;
+        if unwinding {
+            await_cancellations(&cancel_provider, sent).await?;
+        }
+    }
+    let Some(summary) = outcome? else {
+        return Ok(());
+    };
+    if opts.dry_sign {
+        // stdout is just the signed transactions
+        return Ok(());
+    }
+    if opts.log_summary {
+        log_summary(&summary)?;
+    }
+    summary.emit(opts.output_format, &opts.summary_csv)?;
+    Ok(())
+}
+
+const CONDITIONAL_SEND_METHOD: &str = "eth_sendRawTransactionConditional";
+
+/// nodes don't agree on an error code for unknown methods, so match on the message
+fn is_unsupported_method(err: &ProviderError) -> bool {
+    let msg = err.to_string().to_lowercase();
+    msg.contains("method not found")
+        || msg.contains("does not exist")
+        || msg.contains("not supported")
+        || msg.contains("-32601")
+}
+
+/// what a node answers when a transaction at a pending nonce doesn't pay enough more than it
+fn is_underpriced_replacement(err: &impl std::fmt::Display) -> bool {
+    err.to_string()
+        .to_lowercase()
+        .contains("replacement transaction underpriced")
+}
+
+/// how many times `send_replacing` bumps a transaction before giving up
+const REPLACEMENT_RETRIES: usize = 5;
+
+/// re-sign `txn` paying `bump_pct` percent more per gas, with its sender's wallet from
+/// `wallets`, after the node called it an underpriced replacement
+async fn bump_replacement(
+    wallets: &HashMap<Address, LocalWallet>,
+    txn: &Bytes,
+    bump_pct: u64,
+    attempt: usize,
+) -> eyre::Result<Bytes> {
+    let tx = ethers::utils::rlp::decode::<Transaction>(txn)?;
+    let from = tx.recover_from()?;
+    let wallet = wallets
+        .get(&from)
+        .ok_or_else(|| eyre::eyre!("no signer for {from:?} to bump its gas price"))?;
+    tracing::warn!(
+        "nonce {} of {from:?}: replacement underpriced, retrying {bump_pct}% higher ({attempt}/{REPLACEMENT_RETRIES})",
+        tx.nonce
+    );
+    Ok(bundle_builder::bump_gas_price(&tx, bump_pct, wallet).await?)
+}
+
+/// send `txn` with `send`, bumping its gas price with `bump_replacement` each time the node
+/// says the replacement is underpriced
+async fn send_replacing<F, Fut, E>(
+    send: F,
+    wallets: &HashMap<Address, LocalWallet>,
+    mut txn: Bytes,
+    bump_pct: u64,
+) -> eyre::Result<TxHash>
+where
+    F: Fn(Bytes) -> Fut,
+    Fut: std::future::Future<Output = Result<TxHash, E>>,
+    E: std::fmt::Display,
+    eyre::Report: From<E>,
+{
+    let mut retries = 0;
+    loop {
+        match send(txn.clone()).await {
+            Err(err) if retries < REPLACEMENT_RETRIES && is_underpriced_replacement(&err) => {
+                retries += 1;
+                txn = bump_replacement(wallets, &txn, bump_pct, retries).await?;
+            }
+            result => return Ok(result?),
+        }
+    }
+}
+
+/// go through the mempool, for transactions with <= 128kb of calldata each
+async fn submit_txns(
+    provider: SignerMiddleware<Arc<Provider<Http>>, Wallet<SigningKey>>,
+    extra_signers: Vec<LocalWallet>,
+    params: &bundle_builder::TxParams,
+    nonces: &nonce::NonceManager,
+    opts: &Opts,
+) -> eyre::Result<summary::Summary> {
+    let mut landed = 0;
+    let chunk_size = opts.chunk_size;
+    let mempool_txs = opts.mempool_txs;
+    bundle_builder::validate_chunk_size(chunk_size, true)?;
+    let started = Instant::now();
+    let mut summary = summary::Summary::new("mempool", opts.fill_pct, chunk_size, 0);
+    summary.label = opts.label.clone();
+    let calldata_bytes = bundle_builder::chunk_calldata_bytes(chunk_size);
+
+    let default_gas_price = match params.gas_price_snapshot {
+        Some(snapshot) => snapshot,
+        None => bundle_builder::network_gas_price(params, &provider).await?,
+    };
+
+    if opts.duplicate_nonce {
+        let landed = submit_duplicate_nonces(
+            &provider,
+            params,
+            nonces.reserve(params.address, mempool_txs as u64),
+            calldata_bytes,
+            default_gas_price,
+            mempool_txs,
+        )
+        .await?;
+        summary.end_nonce = nonces.current(params.address).as_u64();
+        summary.finish(landed, mempool_txs, started.elapsed());
+        return Ok(summary);
+    }
+
+    // every signer with the params its transactions are signed with
+    let mut senders = vec![(provider.clone(), params.clone())];
+    for wallet in extra_signers {
+        let address = wallet.address();
+        nonces
+            .resync(address, &provider, opts.nonce_block.into())
+            .await?;
+        let sender = SignerMiddleware::new(provider.inner().clone(), wallet);
+        let params = bundle_builder::TxParams {
+            address,
+            ..params.clone()
+        };
+        senders.push((sender, params));
+    }
+    if opts.balance_check_every.is_some() {
+        let mut funded = Vec::new();
+        for sender in senders {
+            if balance_ok(&provider, sender.1.address, opts).await? || !opts.stop_on_low_balance {
+                funded.push(sender);
+            } else {
+                tracing::warn!("not sending from {:?}", sender.1.address);
+            }
+        }
+        senders = funded;
+        if senders.is_empty() {
+            eyre::bail!("every signer is below --low-balance-eth");
+        }
+    }
+    let addresses: Vec<Address> = senders.iter().map(|(_, params)| params.address).collect();
+    let slots = schedule::schedule(senders.len(), mempool_txs - 1, opts.nonce_distribution);
+    let counts = schedule::per_signer_counts(&slots, senders.len());
+    if !opts.dry_sign {
+        for ((_, params), count) in senders.iter().zip(&counts) {
+            check_value_balance(&provider, params.address, *count, opts).await?;
+        }
+    }
+    if senders.len() > 1 {
+        for ((_, params), count) in senders.iter().zip(counts) {
+            tracing::info!(
+                "{:?} sends {count} transactions from nonce {}",
+                params.address,
+                nonces.current(params.address)
+            );
+        }
+    }
+
+    let mut transactions: Vec<Bytes> = Vec::new();
+    // nonce, value and gas price of each signed transaction
+    let mut sent_nonces = Vec::new();
+    let mut sent_values = Vec::new();
+    let mut sent_gas_prices = Vec::new();
+
+    // worst case cost of everything signed so far, for `--max-fee-cap-eth`
+    let mut max_cost = U256::zero();
+    for slot in &slots {
+        let (sender, sender_params) = &senders[slot.signer];
+        // slots come in nonce order per signer, so taking the next nonce matches `nonce_offset`
+        let new_nonce = nonces.next(sender_params.address);
+        let tx = bundle_builder::get_signed_tx(
+            sender_params,
+            calldata_bytes,
+            default_gas_price,
+            None,
+            sender.clone(),
+            new_nonce, //*nonce,
+        )
+        .await?;
+        let decoded = ethers::utils::rlp::decode::<Transaction>(&tx)?;
+        if let Some(cap) = opts.max_fee_cap_eth {
+            max_cost += decoded.gas * decoded.gas_price.unwrap_or_default();
+            if max_cost > cap {
+                tracing::warn!(
+                    "--max-fee-cap-eth reached, only sending {} of {mempool_txs} transactions",
+                    transactions.len()
+                );
+                // not sent, so later transactions from this signer can have it
+                nonces.set(sender_params.address, new_nonce);
+                break;
+            }
+        }
+        transactions.push(tx);
+        sent_nonces.push(new_nonce);
+        sent_values.push(decoded.value);
+        sent_gas_prices.push(decoded.gas_price.unwrap_or_default());
+    }
+    tracing::debug!("generated {mempool_txs} transactions");
+    if opts.gas_price_jitter_pct > 0.0 {
+        let decoded = transactions
+            .iter()
+            .map(|tx| ethers::utils::rlp::decode::<Transaction>(tx))
+            .collect::<Result<Vec<_>, _>>()?;
+        if let Some((low, high)) = bundle_builder::gas_price_range(&decoded) {
+            tracing::info!(
+                "gas price jitter ±{}%: transactions offer {low} to {high} wei per gas",
+                opts.gas_price_jitter_pct
+            );
+        }
+    }
+    if opts.dry_sign {
+        print_signed(&transactions);
+        return Ok(summary);
+    }
+
+    let mut conditional = opts.conditional_block_range.map(|(min, max)| {
+        json!({
+            "blockNumberMin": U64::from(min),
+            "blockNumberMax": U64::from(max),
+        })
+    });
+
+    // probe with the first transaction, so we know whether to fall back before sending the rest
+    let mut pending_txs = Vec::new();
+    if let (Some(options), Some(first)) = (&conditional, transactions.first()) {
+        match provider
+            .provider()
+            .request::<_, TxHash>(CONDITIONAL_SEND_METHOD, (first, options))
+            .await
+        {
+            Ok(hash) => {
+                tracing::debug!("node accepted conditional transaction {hash:?}");
+                pending_txs.push(PendingTransaction::new(hash, provider.provider()));
+                transactions.remove(0);
+            }
+            Err(err) if is_unsupported_method(&err) => {
+                tracing::warn!("{CONDITIONAL_SEND_METHOD} not supported ({err}), falling back to eth_sendRawTransaction");
+                conditional = None;
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    // every signer's wallet, for re-signing underpriced replacements
+    let wallets: HashMap<Address, LocalWallet> = senders
+        .iter()
+        .map(|(sender, params)| (params.address, sender.signer().clone()))
+        .collect();
+    let concurrency = opts.send_concurrency as usize;
+    let semaphore = Semaphore::new(concurrency);
+    let send = |txn: Bytes| {
+        let conditional = conditional.as_ref();
+        let provider = &provider;
+        let semaphore = &semaphore;
+        let wallets = &wallets;
+        async move {
+            if semaphore.available_permits() == 0 {
+                tracing::debug!("all {concurrency} sends in flight, waiting for one to finish");
+            }
+            let _permit = semaphore.acquire().await?;
+            let in_flight = concurrency - semaphore.available_permits();
+            tracing::debug!("sending transaction, {in_flight}/{concurrency} sends in flight");
+            let rpc = provider.provider();
+            let hash = match conditional {
+                Some(options) => {
+                    let send = |txn: Bytes| {
+                        rpc.request::<_, TxHash>(CONDITIONAL_SEND_METHOD, (txn, options))
+                    };
+                    send_replacing(send, wallets, txn, opts.replacement_bump_pct).await?
+                }
+                None => {
+                    let send = |txn: Bytes| async move {
+                        rpc.send_raw_transaction(txn).await.map(|pending| *pending)
+                    };
+                    send_replacing(send, wallets, txn, opts.replacement_bump_pct).await?
+                }
+            };
+            Ok::<_, eyre::Report>(PendingTransaction::new(hash, rpc))
+        }
+    };
+
+    if let Some(rpc_batch_size) = opts.rpc_batch_size {
+        let headers = opts.rpc_header.iter().cloned().collect();
+        let batcher = rpc_batch::BatchSender::new(Url::parse(&opts.rpc_url)?, headers)?;
+        let mut batched = 0;
+        for batch in transactions.chunks(rpc_batch_size as usize) {
+            match batcher.send_raw_transactions(batch).await {
+                Ok(results) => {
+                    for (txn, result) in batch.iter().zip(results) {
+                        match result {
+                            Ok(hash) => {
+                                pending_txs.push(PendingTransaction::new(hash, provider.provider()))
+                            }
+                            // bump it once here, `send` takes it from there
+                            Err(reason) if is_underpriced_replacement(&reason) => {
+                                let bumped =
+                                    bump_replacement(&wallets, txn, opts.replacement_bump_pct, 1)
+                                        .await?;
+                                pending_txs.push(send(bumped).await?);
+                            }
+                            Err(reason) => {
+                                eyre::bail!("node rejected a batched transaction: {reason}")
+                            }
+                        }
+                    }
+                    batched += batch.len();
+                }
+                Err(err) => {
+                    tracing::warn!("{err}, falling back to a request per transaction");
+                    break;
+                }
+            }
+        }
+        tracing::info!("sent {batched} transactions in json-rpc batches of up to {rpc_batch_size}");
+        // batches go out in order, so whatever's left is the tail
+        transactions.drain(..batched);
+    }
+
+    match opts.batch_size {
+        None => pending_txs
+            .extend(futures::future::try_join_all(transactions.into_iter().map(send)).await?),
+        Some(batch_size) => {
+            // each wave only has to be accepted by the node, not mined, before the next goes out
+            let waves = transactions.chunks(batch_size as usize);
+            let total_waves = waves.len();
+            let mut balances_checked_at = provider.get_block_number().await?;
+            for (wave, batch) in waves.enumerate() {
+                if let Some(every) = opts.balance_check_every {
+                    let block = provider.get_block_number().await?;
+                    if block >= balances_checked_at + every {
+                        balances_checked_at = block;
+                        if !balances_ok(&provider, &addresses, opts).await?
+                            && opts.stop_on_low_balance
+                        {
+                            tracing::warn!(
+                                "stopping after wave {wave}/{total_waves}, a signer is below --low-balance-eth"
+                            );
+                            break;
+                        }
+                    }
+                }
+                let results = futures::future::join_all(batch.iter().cloned().map(send)).await;
+                let accepted = results.iter().filter(|result| result.is_ok()).count();
+                tracing::info!(
+                    "wave {}/{total_waves}: {accepted}/{} transactions accepted",
+                    wave + 1,
+                    batch.len()
+                );
+                for result in results {
+                    pending_txs.push(result?);
+                }
+            }
+        }
+    }
+
+    // the transactions a stop left unsent, handing their nonces back to their signers
+    let mut rewound = HashSet::new();
+    for (slot, nonce) in slots.iter().zip(&sent_nonces).skip(pending_txs.len()) {
+        let address = senders[slot.signer].1.address;
+        if rewound.insert(address) {
+            nonces.set(address, *nonce);
+        }
+    }
+
+    // nonces were recorded in submission order, so they line up with the pending hashes
+    let watched: Vec<(TxHash, U256, U256)> = pending_txs
+        .iter()
+        .zip(sent_nonces.iter().zip(&sent_gas_prices))
+        .map(|(pending, (nonce, gas_price))| (**pending, *nonce, *gas_price))
+        .collect();
+    let (receipts, evicted) = tokio::select! {
+        awaited = futures::future::join(
+            futures::future::try_join_all(pending_txs),
+            watch_evictions(&provider, watched),
+        ) => awaited,
+        () = watch_balances(&provider, &addresses, opts) => unreachable!("watch_balances never resolves"),
+    };
+    let receipts: Vec<Option<TransactionReceipt>> = receipts?;
+
+    tracing::debug!("submitted {mempool_txs} transactions");
+    let attempted = receipts.len();
+    let end_nonce = nonces.current(params.address).as_u64();
+    let mut blocks = BTreeSet::new();
+    let mut spent = U256::zero();
+
+    let mut value_sent = U256::zero();
+    for ((receipt, nonce), value) in receipts.into_iter().zip(&sent_nonces).zip(&sent_values) {
+        thread::sleep(time::Duration::from_millis(20));
+        if let Some(receipt) = receipt {
+            if let Some(confirmations) = opts.confirm_blocks {
+                let block = receipt.block_number.unwrap_or_default();
+                let hash = receipt.transaction_hash;
+                if !wait_for_confirmations(&provider, hash, block, confirmations).await? {
+                    tracing::warn!(
+                        "{hash:?} left block {block} before {confirmations} confirmations, not counted as landed"
+                    );
+                    continue;
+                }
+            }
+            // not hitting this should be rare - somehow get dropped from mempool if gas too low
+            landed += 1;
+            tracing::info!(
+                "{} {landed} on {}",
+                receipt.transaction_hash,
+                receipt.block_number.unwrap_or_default()
+            );
+            blocks.insert(receipt.block_number);
+            spent += receipt_cost(&receipt);
+            value_sent += *value;
+            summary.value_sent_eth = wei_to_eth(value_sent)?;
+            if let Some(path) = &opts.export_landed_txs {
+                let block = receipt.block_number.unwrap_or_default();
+                export_landed_tx(path, receipt.transaction_hash, block, *nonce)?;
+            }
+            if let Some(path) = &opts.dump_receipts {
+                dump_receipt(path, &receipt)?;
+            }
+            summary.blocks = blocks.len() as u64;
+            summary.spent_eth = wei_to_eth(spent)?;
+            summary.end_nonce = end_nonce;
+            summary.finish(landed, attempted, started.elapsed());
+            summary::record_partial(&summary, receipt.block_number.unwrap_or_default().as_u64());
+            log_txn(receipt);
+        } else {
+            tracing::debug!("no receipt!");
+        }
+    }
+
+    summary.blocks = blocks.len() as u64;
+    summary.spent_eth = wei_to_eth(spent)?;
+    summary.evicted = evicted;
+    summary.end_nonce = end_nonce;
+    summary.finish(landed, attempted, started.elapsed());
+    Ok(summary)
+}
+
+/// how often to poll submitted mempool transactions for eviction
+const EVICTION_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// stop watching a transaction the node still hasn't reported as pending after this many
+/// polls, e.g. one that went to another node behind a load balancer
+const EVICTION_UNSEEN_POLLS: usize = 30;
+
+/// poll `eth_getTransactionByHash` until every transaction is either mined or gone.  one that
+/// we've seen pending and then disappears got evicted from the mempool.  one that's never
+/// seen is given up on after `EVICTION_UNSEEN_POLLS` polls.  `watched` is each transaction's
+/// hash, nonce and gas price, the price updated from the node once it's seen, since a bumped
+/// replacement pays more than what was signed.  a failed poll is retried on the next one, up
+/// to `EVICTION_UNSEEN_POLLS` in a row before it settles for the evictions counted so far.
+async fn watch_evictions<M: Middleware>(
+    provider: &M,
+    mut watched: Vec<(TxHash, U256, U256)>,
+) -> usize
+where
+    M::Error: 'static,
+{
+    let mut seen = vec![false; watched.len()];
+    let mut evicted = 0;
+    let mut polls = 0;
+    let mut failed_polls = 0;
+    while !watched.is_empty() {
+        tokio::time::sleep(EVICTION_POLL_INTERVAL).await;
+        let lookups = match futures::future::try_join_all(
+            watched
+                .iter()
+                .map(|(hash, _, _)| provider.get_transaction(*hash)),
+        )
+        .await
+        {
+            Ok(lookups) => lookups,
+            Err(err) if failed_polls < EVICTION_UNSEEN_POLLS => {
+                failed_polls += 1;
+                tracing::debug!("{err}, looking for evictions again on the next poll");
+                continue;
+            }
+            Err(err) => {
+                tracing::warn!("{err}, giving up on watching for evictions after {evicted}");
+                break;
+            }
+        };
+        polls += 1;
+        failed_polls = 0;
+
+        let mut still_pending = Vec::new();
+        let mut still_seen = Vec::new();
+        for (((hash, nonce, gas_price), was_seen), tx) in watched.into_iter().zip(seen).zip(lookups)
+        {
+            match tx {
+                Some(tx) if tx.block_number.is_some() => {}
+                Some(tx) => {
+                    let gas_price = tx.gas_price.unwrap_or(gas_price);
+                    still_pending.push((hash, nonce, gas_price));
+                    still_seen.push(true);
+                }
+                None if was_seen => {
+                    evicted += 1;
+                    tracing::warn!(
+                        "evicted: {hash:?} (nonce {nonce}, gas price {gas_price}) dropped from the mempool"
+                    );
+                    log_eviction(hash, nonce, gas_price);
+                }
+                None if polls >= EVICTION_UNSEEN_POLLS => {
+                    tracing::debug!(
+                        "never saw {hash:?} (nonce {nonce}) pending on this node, not watching it for eviction"
+                    );
+                }
+                None => {
+                    // not propagated to this node yet, keep looking
+                    still_pending.push((hash, nonce, gas_price));
+                    still_seen.push(false);
+                }
+            }
+        }
+        watched = still_pending;
+        seen = still_seen;
+    }
+    evicted
+}
+
+/// what `--cancel-on-exit` pays per gas, as a percentage of the network gas price: well above
+/// the bump a node wants to replace a transaction signed at the network price
+const CANCEL_GAS_PRICE_PCT: u64 = 200;
+
+/// resolves on SIGUSR1, for `--cancel-on-signal`
+#[cfg(unix)]
+async fn cancel_signal() -> eyre::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+    signal(SignalKind::user_defined1())?.recv().await;
+    Ok(())
+}
+
+/// there's no SIGUSR1 to wait for
+#[cfg(not(unix))]
+async fn cancel_signal() -> eyre::Result<()> {
+    std::future::pending().await
+}
+
+/// replace each signer's transactions from its mined nonce up to the next nonce it would have
+/// been handed with zero-value self-transfers, so none of them are left pending.  returns the
+/// signer, nonce and hash of each replacement sent.
+async fn cancel_outstanding(
+    provider: &Arc<Provider<Http>>,
+    signers: Vec<LocalWallet>,
+    nonces: &nonce::NonceManager,
+    chain_id: u64,
+) -> eyre::Result<Vec<(Address, U256, TxHash)>> {
+    let mut sent = Vec::new();
+    let gas_price = provider.get_gas_price().await? * CANCEL_GAS_PRICE_PCT / 100;
+    for wallet in signers {
+        let address = wallet.address();
+        let mined = provider
+            .get_transaction_count(address, Some(BlockNumber::Latest.into()))
+            .await?;
+        let next = nonces.current(address);
+        if mined >= next {
+            continue;
+        }
+        let client = SignerMiddleware::new(provider.clone(), wallet.with_chain_id(chain_id));
+        let mut nonce = mined;
+        while nonce < next {
+            let cancel = TransactionRequest::new()
+                .from(address)
+                .to(address)
+                .value(0)
+                .gas(21_000)
+                .gas_price(gas_price)
+                .nonce(nonce);
+            match client.send_transaction(cancel, None).await {
+                Ok(pending) => {
+                    tracing::info!(
+                        "cancelling nonce {nonce} of {address:?} with {:?} at {gas_price} wei",
+                        *pending
+                    );
+                    sent.push((address, nonce, *pending));
+                }
+                // most likely the original was mined in the meantime
+                Err(err) => tracing::warn!("couldn't cancel nonce {nonce} of {address:?}: {err}"),
+            }
+            nonce += U256::one();
+        }
+    }
+    Ok(sent)
+}
+
+/// how long `--cancel-on-signal` waits for its cancellations before giving up on them
+const CANCEL_WAIT: Duration = Duration::from_secs(120);
+
+/// wait for each cancellation from `cancel_outstanding` to be mined, or for its nonce to be
+/// used up by the original, and log which it was
+async fn await_cancellations(
+    provider: &Arc<Provider<Http>>,
+    mut pending: Vec<(Address, U256, TxHash)>,
+) -> eyre::Result<()> {
+    let total = pending.len();
+    let (mut cancelled, mut originals) = (0, 0);
+    let started = Instant::now();
+    while !pending.is_empty() && started.elapsed() < CANCEL_WAIT {
+        tokio::time::sleep(CONFIRMATION_POLL_INTERVAL).await;
+        let mut still_pending = Vec::new();
+        for (address, nonce, hash) in pending {
+            if let Some(receipt) = provider.get_transaction_receipt(hash).await? {
+                cancelled += 1;
+                tracing::info!(
+                    "cancelled nonce {nonce} of {address:?}: {hash:?} mined in block {}",
+                    receipt.block_number.unwrap_or_default()
+                );
+                continue;
+            }
+            let mined = provider
+                .get_transaction_count(address, Some(BlockNumber::Latest.into()))
+                .await?;
+            if mined > nonce {
+                originals += 1;
+                tracing::info!(
+                    "nonce {nonce} of {address:?} wasn't cancelled, the original landed first"
+                );
+            } else {
+                still_pending.push((address, nonce, hash));
+            }
+        }
+        pending = still_pending;
+    }
+    for (address, nonce, hash) in &pending {
+        tracing::warn!("cancellation {hash:?} of nonce {nonce} of {address:?} is still pending");
+    }
+    tracing::info!(
+        "cancelled {cancelled}/{total} in-flight transactions, {originals} originals landed first, {} still pending",
+        pending.len()
+    );
+    Ok(())
 }
 
-fn log_txn(txn: TransactionReceipt) {
-    let _entry = get_txn_json(txn);
-    let mut file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open("stress-4844-mempool-txns.json")
-        .unwrap();
+/// how much more the replacement in a duplicate-nonce pair pays.  geth wants at least 10%
+/// to accept a replacement, so leave some room above that.
+const DUPLICATE_NONCE_BUMP_PCT: u64 = 20;
 
-    let _res = file.write_all(b"\n");
-    let res = serde_json::to_writer(file, &_entry);
+/// send two transactions per nonce, the second one paying more, and report which one landed
+async fn submit_duplicate_nonces(
+    provider: &SignerMiddleware<Arc<Provider<Http>>, Wallet<SigningKey>>,
+    params: &bundle_builder::TxParams,
+    nonce: U256,
+    calldata_bytes: usize,
+    gas_price: U256,
+    mempool_txs: usize,
+) -> eyre::Result<usize> {
+    let mut landed = 0;
+    let bumped_gas_price = gas_price * (100 + DUPLICATE_NONCE_BUMP_PCT) / 100;
 
-    match res {
-        Err(e) => eprintln!("Couldn't write to file: {}", e),
-        Ok(_) => {
-            return;
+    let mut originals = Vec::new();
+    let mut replacements = Vec::new();
+    for i in 0..mempool_txs {
+        let new_nonce = nonce + U256::from(i);
+        for (price, txs) in [
+            (gas_price, &mut originals),
+            (bumped_gas_price, &mut replacements),
+        ] {
+            let tx = bundle_builder::get_signed_tx(
+                params,
+                calldata_bytes,
+                price,
+                None,
+                provider,
+                new_nonce,
+            )
+            .await?;
+            txs.push(tx);
         }
     }
-}
-
-/// Address of the following contract to allow for easy coinbase payments on Goerli.
-///
-/// contract CoinbasePayer {
-///     receive() payable external {
-///         payable(address(block.coinbase)).transfer(msg.value);
-///     }
-/// }
-// const COINBASE_PAYER_ADDR: &str = "0x060d6635bb76c71871f97C12f10Fa20BD8e87eC0";
-
-#[tokio::main]
-async fn main() -> eyre::Result<()> {
-    let opts = Opts::parse();
+    tracing::debug!(
+        "generated {mempool_txs} duplicate-nonce pairs at {gas_price} / {bumped_gas_price} wei"
+    );
 
-    let rpc_url = opts.rpc_url;
+    // originals go out first so the node sees the second of each pair as a replacement
+    let pending_originals = futures::future::try_join_all(
+        originals
+            .into_iter()
+            .map(|txn| provider.send_raw_transaction(txn)),
+    )
+    .await?;
+    let pending_replacements = futures::future::try_join_all(
+        replacements
+            .into_iter()
+            .map(|txn| provider.send_raw_transaction(txn)),
+    )
+    .await?;
 
-    let use_mempool = opts.mem_pool;
+    let hashes: Vec<(TxHash, TxHash)> = pending_originals
+        .iter()
+        .zip(pending_replacements.iter())
+        .map(|(original, replacement)| (**original, **replacement))
+        .collect();
 
-    let tx_signer = opts.tx_signer.strip_prefix("0x").unwrap_or(&opts.tx_signer);
-    let signer = tx_signer.parse::<LocalWallet>()?;
+    // whichever of the pair got dropped resolves to no receipt
+    let (original_receipts, replacement_receipts) = futures::future::try_join(
+        futures::future::try_join_all(pending_originals),
+        futures::future::try_join_all(pending_replacements),
+    )
+    .await?;
 
-    let fill_pct = opts.fill_pct; // how much of the full 2MB payload to take up with calldata
-    let tip_wei = opts.tip_wei; // how much to overpay on gas, in wei.
-    let chunk_size = opts.chunk_size;
+    for (i, ((original, replacement), (original_receipt, replacement_receipt))) in hashes
+        .into_iter()
+        .zip(original_receipts.into_iter().zip(replacement_receipts))
+        .enumerate()
+    {
+        let new_nonce = nonce + U256::from(i);
+        match (original_receipt, replacement_receipt) {
+            (Some(receipt), None) => {
+                tracing::info!(
+                    "nonce {new_nonce}: original {original:?} ({gas_price} wei) landed on {}, replacement {replacement:?} dropped",
+                    receipt.block_number.unwrap()
+                );
+                landed += 1;
+                log_txn(receipt);
+            }
+            (None, Some(receipt)) => {
+                tracing::info!(
+                    "nonce {new_nonce}: replacement {replacement:?} ({bumped_gas_price} wei) landed on {}, original {original:?} dropped",
+                    receipt.block_number.unwrap()
+                );
+                landed += 1;
+                log_txn(receipt);
+            }
+            (Some(_), Some(_)) => {
+                tracing::error!(
+                    "nonce {new_nonce}: both {original:?} and {replacement:?} have receipts!"
+                );
+            }
+            (None, None) => {
+                tracing::debug!("nonce {new_nonce}: neither transaction landed");
+            }
+        }
+    }
 
-    let interval = Duration::from_secs(1);
+    Ok(landed)
+}
 
-    tracing_subscriber::registry()
-        .with(tracing_subscriber::fmt::layer())
-        .with(EnvFilter::new("stress4844=trace"))
-        .init();
+/// whether `tx_hash` is still included in `block` on the canonical chain
+async fn still_canonical<M: Middleware>(
+    provider: &M,
+    tx_hash: TxHash,
+    block: U64,
+) -> eyre::Result<bool>
+where
+    M::Error: 'static,
+{
+    let receipt = provider.get_transaction_receipt(tx_hash).await?;
+    Ok(receipt.and_then(|receipt| receipt.block_number) == Some(block))
+}
 
-    // the "usual" rpc provider, no flashbots mev-boost middleware
-    let provider: Arc<Provider<Http>> =
-        Arc::new(Provider::<Http>::try_from(rpc_url)?.interval(interval));
+/// `--dry-sign` output: each transaction's hash and signed raw hex, one per line
+fn print_signed(transactions: &[Bytes]) {
+    for tx in transactions {
+        println!("{:?} {tx}", H256::from(ethers::utils::keccak256(tx)));
+    }
+}
 
-    let chain_id = provider.get_chainid().await?.as_u64();
+/// wait until `tx_hash`, included in `block`, has `confirmations` confirmations, logging it as
+/// tentative meanwhile.  false if by then it's no longer in `block`.
+async fn wait_for_confirmations<M: Middleware>(
+    provider: &M,
+    tx_hash: TxHash,
+    block: U64,
+    confirmations: u64,
+) -> eyre::Result<bool>
+where
+    M::Error: 'static,
+{
+    // being included in `block` is the first confirmation
+    let final_block = block + confirmations - 1;
+    if provider.get_block_number().await? < final_block {
+        tracing::info!(
+            "{tx_hash:?} tentative in block {block}, waiting for {confirmations} confirmations"
+        );
+        while provider.get_block_number().await? < final_block {
+            tokio::time::sleep(CONFIRMATION_POLL_INTERVAL).await;
+        }
+    }
+    still_canonical(provider, tx_hash, block).await
+}
 
-    let address = signer.address();
-    let balance = provider.get_balance(address, None).await?;
+/// how often `wait_for_confirmations` checks the head block
+const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_secs(2);
 
-    tracing::info!(
-        "starting benchmark from {:?} (balance: {} ETH)",
-        address,
-        ethers::core::utils::format_units(balance, "eth")?,
-    );
+/// sign a fresh bundle at `nonce` (and `normal_nonce`, for `--include-normal-tx`) tipping
+/// `tip_wei`, enforcing `--min-fill-pct` if set
+async fn next_bundle<M: Middleware>(
+    params: &bundle_builder::TxParams,
+    provider: &M,
+    gas_limit: U256,
+    nonce: U256,
+    normal_nonce: Option<U256>,
+    tip_wei: u64,
+    opts: &Opts,
+) -> eyre::Result<BundleRequest>
+where
+    M::Error: 'static,
+{
+    let bundle = bundle_builder::construct_bundle(
+        params,
+        provider,
+        gas_limit,
+        opts.fill_pct,
+        nonce,
+        normal_nonce,
+        opts.chunk_size,
+        tip_wei,
+    )
+    .await?;
+    if let Some(min_fill_pct) = opts.min_fill_pct {
+        bundle_builder::check_min_fill(
+            &bundle,
+            min_fill_pct,
+            opts.chunk_size,
+            gas_limit,
+            params.fork,
+        )?;
+    }
+    Ok(bundle)
+}
 
-    let mut nonce = provider
-        .get_transaction_count(address, Some(BlockNumber::Pending.into()))
-        .await?;
-    tracing::debug!("current nonce: {nonce}, use_mempool = {use_mempool}");
-    // TODO: Do we want this to be different per transaction?
-    let receiver: Address = "0x4844AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA".parse()?;
+/// make sure `--simulation-state-block`, if given, is a block the node has
+async fn check_simulation_state_block<M: Middleware>(provider: &M, opts: &Opts) -> eyre::Result<()>
+where
+    M::Error: 'static,
+{
+    if let Some(state_block) = opts.simulation_state_block {
+        if provider.get_block(state_block).await?.is_none() {
+            eyre::bail!("--simulation-state-block {state_block} doesn't exist on this chain (yet)");
+        }
+        tracing::info!("simulating bundles against the state at block {state_block}");
+    }
+    Ok(())
+}
 
-    let block = provider
-        .get_block(BlockNumber::Latest)
-        .await?
-        .expect("could not get latest block");
+/// `--target-slot`: block `target` has to still be ahead of `head`
+fn ensure_target_ahead(target: u64, head: U64) -> eyre::Result<()> {
+    if U64::from(target) <= head {
+        eyre::bail!("--target-slot {target} is already in the past, the chain is at block {head}");
+    }
+    Ok(())
+}
 
-    if use_mempool {
-        let mempool_txs = opts.mempool_txs;
-        // Sign transactions with a private key
-        let provider = SignerMiddleware::new(provider, signer);
-        submit_txns(
-            provider,
-            chain_id,
-            address,
-            receiver,
-            &mut nonce,
-            chunk_size,
-            mempool_txs,
-        )
-        .await?;
-    } else {
-        let blocks_to_land = opts.blocks;
-        let bundle_signer = opts
-            .bundle_signer
-            .strip_prefix("0x")
-            .unwrap_or(&opts.bundle_signer);
+/// a flashbots relay, signing its requests with that relay's bundle signer
+type Relay = FlashbotsMiddleware<Arc<Provider<Http>>, Wallet<SigningKey>>;
 
-        let bundle_signer = bundle_signer.parse::<LocalWallet>()?;
+/// the tx signer on top of the flashbots relay, as used for bundle submission
+type BundleClient = SignerMiddleware<Relay, Wallet<SigningKey>>;
 
-        submit_bundles(
-            provider,
-            chain_id,
-            address,
-            receiver,
-            &mut nonce,
-            block,
-            blocks_to_land,
-            chunk_size,
-            fill_pct,
-            tip_wei,
-            tx_signer,
-            bundle_signer,
-        )
-        .await?;
+/// simulate `bundle` on top of `block_number`, in a block with `gas_limit`, and rebuild it
+/// without the transactions the relay reports as reverted or failed.
+async fn exclude_reverting(
+    params: &bundle_builder::TxParams,
+    provider: &BundleClient,
+    bundle: BundleRequest,
+    block_number: U64,
+    gas_limit: U256,
+    nonce: U256,
+) -> eyre::Result<BundleRequest> {
+    let simulation = bundle
+        .clone()
+        .set_block(block_number + 1)
+        .set_simulation_block(block_number)
+        .set_simulation_timestamp(0);
+    let simulated = simulate::call_bundle(provider.inner(), &simulation, gas_limit).await?;
+    let reverting: BTreeSet<usize> = simulated
+        .transactions
+        .iter()
+        .enumerate()
+        .filter(|(_, tx)| tx.error.is_some() || tx.revert.is_some())
+        .map(|(i, _)| i)
+        .collect();
+    if reverting.is_empty() {
+        return Ok(bundle);
     }
-    Ok(())
+    tracing::info!(
+        "dropping {} of {} transactions that revert in simulation",
+        reverting.len(),
+        bundle.transactions().len()
+    );
+    let kept =
+        bundle_builder::drop_transactions(params, provider, &bundle, &reverting, nonce).await?;
+    if kept.transactions().is_empty() {
+        eyre::bail!("every transaction in the bundle reverts in simulation, check --receiver");
+    }
+    Ok(kept)
 }
 
-/// go through the mempool, for transactions with <= 128kb of calldata each
-async fn submit_txns(
-    provider: SignerMiddleware<Arc<Provider<Http>>, Wallet<SigningKey>>,
-    chain_id: u64,
-    address: H160,
-    receiver: H160,
-    nonce: &mut U256,
-    chunk_size: usize,
+/// sleep before relay retry number `attempt` (from 1)
+async fn relay_backoff(attempt: u32, opts: &Opts) {
+    let backoff = opts
+        .relay_retry_backoff_ms
+        .saturating_mul(1 << (attempt - 1).min(16));
+    tokio::time::sleep(Duration::from_millis(backoff)).await;
+}
 
-    mempool_txs: usize,
-) -> eyre::Result<()> {
-    let mut landed = 0;
-    let calldata_bytes = bundle_builder::calldata_kb_to_bytes(chunk_size);
+/// the relay's answer to `eth_sendBundle` (and a MEV-Share node's to `mev_sendBundle`)
+#[derive(Deserialize)]
+struct SendBundleResponse {
+    #[serde(rename = "bundleHash")]
+    bundle_hash: BundleHash,
+}
 
-    let default_gas_price = provider.get_gas_price().await?;
+/// a bundle with its `builders` field set, which `BundleRequest` itself has no room for
+#[derive(Serialize)]
+struct TargetedBundle<'a> {
+    #[serde(flatten)]
+    bundle: &'a BundleRequest,
+    builders: &'a [String],
+}
 
-    let mut transactions: Vec<Bytes> = Vec::new();
+/// `send_bundle`, but only shared with `builders`
+async fn send_to_builders<'a>(
+    relay: &'a Relay,
+    bundle: &BundleRequest,
+    builders: &[String],
+) -> Result<PendingBundle<'a, Http>, FlashbotsMiddlewareError<Arc<Provider<Http>>, LocalWallet>> {
+    let block = bundle
+        .block()
+        .ok_or(FlashbotsMiddlewareError::MissingParameters)?;
+    let response: SendBundleResponse = relay
+        .relay()
+        .request("eth_sendBundle", [TargetedBundle { bundle, builders }])
+        .await
+        .map_err(FlashbotsMiddlewareError::RelayError)?;
+    Ok(PendingBundle::new(
+        response.bundle_hash,
+        block,
+        bundle.transaction_hashes(),
+        relay.provider(),
+    ))
+}
 
-    for i in 0..mempool_txs - 1 {
-        let new_nonce = *nonce + U256::from(i);
-        let tx = bundle_builder::get_signed_tx(
-            chain_id,
-            address,
-            receiver,
-            calldata_bytes,
-            default_gas_price,
-            provider.clone(),
-            new_nonce, //*nonce,
-        )
-        .await?;
-        transactions.push(tx);
+/// `send_bundle`, retrying errors from the relay itself within `--relay-timeout-retries`
+async fn send_with_retries<'a>(
+    relay: &'a Relay,
+    bundle: &BundleRequest,
+    opts: &Opts,
+) -> Result<PendingBundle<'a, Http>, StressError> {
+    let mut attempt = 0;
+    loop {
+        let sent = if opts.use_mev_share {
+            mev_share::send(relay, bundle, &opts.mev_share_hints, &opts.target_builders).await
+        } else if opts.target_builders.is_empty() {
+            relay.send_bundle(bundle).await
+        } else {
+            send_to_builders(relay, bundle, &opts.target_builders).await
+        };
+        match sent {
+            Ok(pending) => return Ok(pending),
+            Err(FlashbotsMiddlewareError::RelayError(err))
+                if attempt < opts.relay_timeout_retries =>
+            {
+                attempt += 1;
+                tracing::warn!(
+                    "relay rejected the bundle ({err}), using relay retry {attempt}/{}",
+                    opts.relay_timeout_retries
+                );
+                relay_backoff(attempt, opts).await;
+            }
+            Err(FlashbotsMiddlewareError::RelayError(err)) => return Err(StressError::relay(err)),
+            Err(err) => {
+                tracing::error!("rpc error sending the bundle, not retried: {err}");
+                return Err(StressError::rpc(err));
+            }
+        }
     }
-    tracing::debug!("generated {mempool_txs} transactions");
-
-    let mut responses = Vec::new();
-    for txn in transactions {
-        let res = provider.send_raw_transaction(txn);
+}
 
-        responses.push(res);
+/// `send_with_retries`, once one of the `--max-concurrent-bundles` `slots` is free
+async fn send_throttled<'a>(
+    slots: &Semaphore,
+    relay: &'a Relay,
+    bundle: &BundleRequest,
+    opts: &Opts,
+) -> Result<PendingBundle<'a, Http>, StressError> {
+    if slots.available_permits() == 0 {
+        tracing::info!(
+            "{} bundle submissions in flight, waiting for one to finish",
+            opts.max_concurrent_bundles
+        );
     }
-    let pending_txs = futures::future::try_join_all(responses).await?;
-    let receipts: Vec<Option<TransactionReceipt>> =
-        futures::future::try_join_all(pending_txs).await?;
-
-    tracing::debug!("submitted {mempool_txs} transactions");
+    let _permit = slots
+        .acquire()
+        .await
+        .expect("bundle slots are never closed");
+    send_with_retries(relay, bundle, opts).await
+}
 
-    for receipt in receipts {
-        thread::sleep(time::Duration::from_millis(20));
-        if let Some(receipt) = receipt {
-            // not hitting this should be rare - somehow get dropped from mempool if gas too low
-            landed += 1;
-            tracing::info!(
-                "{} {landed} on {}",
-                receipt.transaction_hash,
-                receipt.block_number.unwrap()
-            );
-            log_txn(receipt);
-        } else {
-            tracing::debug!("no receipt!");
+/// wait for `pending` to land.  a failed inclusion check is retried within
+/// `--relay-timeout-retries`, a bundle that simply missed its block is not.
+async fn await_inclusion(
+    provider: &BundleClient,
+    mut pending: PendingBundle<'_, Http>,
+    opts: &Opts,
+) -> Result<BundleHash, PendingBundleError> {
+    let (bundle_hash, block, transactions) = (
+        pending.bundle_hash,
+        pending.block,
+        pending.transactions.clone(),
+    );
+    let mut attempt = 0;
+    loop {
+        match pending.await {
+            Err(PendingBundleError::ProviderError(err)) if attempt < opts.relay_timeout_retries => {
+                attempt += 1;
+                tracing::warn!(
+                    "checking bundle inclusion failed ({err}), using relay retry {attempt}/{}",
+                    opts.relay_timeout_retries
+                );
+                relay_backoff(attempt, opts).await;
+                pending = PendingBundle::new(
+                    bundle_hash,
+                    block,
+                    transactions.clone(),
+                    provider.provider(),
+                );
+            }
+            outcome => return outcome,
         }
     }
+}
 
-    Ok(())
+/// unwrap `$result` inside the bundle submission loop.  an error skips to the next block with
+/// `--continue-on-error`, and aborts the run without it.
+macro_rules! or_next_block {
+    ($result:expr, $opts:expr) => {
+        match $result {
+            Ok(value) => value,
+            Err(err) if $opts.continue_on_error => {
+                tracing::error!("{err}, continuing with the next block");
+                continue;
+            }
+            Err(err) => return Err(err.into()),
+        }
+    };
 }
 
 /// go through mev-boost via flashbots relay, potentially for larger calldata txns
+#[allow(clippy::too_many_arguments)]
 async fn submit_bundles(
     provider: Arc<Provider<Http>>,
-    chain_id: u64,
-    address: H160,
-    receiver: H160,
-    nonce: &mut U256,
+    params: &bundle_builder::TxParams,
+    nonces: &nonce::NonceManager,
     block: Block<H256>,
-    blocks_to_land: usize,
-    chunk_size: usize,
-    fill_pct: u8,
-    tip_wei: u64,
-    tx_signer: &str,
-    bundle_signer: Wallet<SigningKey>,
-) -> eyre::Result<()> {
+    signer: Wallet<SigningKey>,
+    relays: Vec<relays::RelayEntry>,
+    show_progress: bool,
+    opts: &Opts,
+) -> eyre::Result<summary::Summary> {
+    if let Some(target) = opts.target_slot {
+        ensure_target_ahead(target, block.number.unwrap_or_default())?;
+        tracing::info!("--target-slot: submitting a single bundle for block {target}");
+    }
     let mut landed = 0;
+    let mut attempted = 0;
+    let mut blocks_seen: u64 = 0;
+    let started = Instant::now();
+    let progress = progress::Progress::new(show_progress);
+    let mut profile = profile::Profile::default();
+    // landed bundles waiting for `reorg_confirmations`: (block landed in, first tx hash)
+    let mut unconfirmed: VecDeque<(U64, TxHash)> = VecDeque::new();
+    // whose balances `--balance-check-every` watches: the tx signer, and the normal tx signer
+    let signers: Vec<Address> = std::iter::once(params.address)
+        .chain(
+            params
+                .normal_tx_signer
+                .as_ref()
+                .map(|signer| signer.address()),
+        )
+        .collect();
 
-    let signer = tx_signer.parse::<LocalWallet>()?;
+    let blocks_to_land = opts.blocks;
+    let chunk_size = opts.chunk_size;
+    let fill_pct = opts.fill_pct; // how much of the block's calldata capacity to take up
+    let mut tip_wei = opts.tip_wei; // how much to overpay on gas, in wei.
+
+    // `--discover-min-tip`: landings in a row at the current tip, and the tip once it's enough
+    let mut landed_streak = 0;
+    // `--keep-bundle-until-landed`: blocks the current signed bundle has missed
+    let mut kept_for = 0;
+    let mut discovered_min_tip = None;
 
     let bundle_middleware = FlashbotsMiddleware::new(
         provider.clone(),
-        Url::parse("https://relay-goerli.flashbots.net/")?, // TODO: make configurable
-        bundle_signer,
+        relays[0].url.clone(),
+        relays[0].signer.clone(),
     );
+    // every other relay gets the same bundles, but only the first one's is awaited
+    let bundle_slots = Semaphore::new(opts.max_concurrent_bundles as usize);
+    let extra_relays: Vec<Relay> = relays[1..]
+        .iter()
+        .map(|relay| {
+            FlashbotsMiddleware::new(provider.clone(), relay.url.clone(), relay.signer.clone())
+        })
+        .collect();
 
     let provider =
         Arc::new(SignerMiddleware::new_with_provider_chain(bundle_middleware, signer).await?);
 
-    let mut bundle = bundle_builder::construct_bundle(
-        chain_id,
-        address,
-        receiver,
-        provider.clone(),
+    if let Some(addr) = opts.metrics_addr {
+        metrics::serve(addr).await?;
+    }
+    tracing::info!("bundle transactions in {:?} order", opts.bundle_order);
+    if opts.no_simulation {
+        tracing::info!("--no-simulation: bundles are sent without relay simulation parameters");
+    }
+
+    if opts.simulate_gas_limit.is_some() && !opts.exclude_reverting {
+        tracing::warn!(
+            "ignoring --simulate-gas-limit: a run only simulates bundles itself with --exclude-reverting"
+        );
+    }
+
+    check_simulation_state_block(&provider, opts).await?;
+    // the state the relay simulates bundles against
+    let simulation_block = |head: U64| opts.simulation_state_block.map(U64::from).unwrap_or(head);
+    let simulation_gas_limit = |latest: &Block<H256>| {
+        opts.simulate_gas_limit
+            .map(U256::from)
+            .unwrap_or(latest.gas_limit)
+    };
+    // `--include-normal-tx`: the transfer's nonce, on its own sequence apart from the calldata
+    let normal_nonce = || {
+        params
+            .normal_tx_signer
+            .as_ref()
+            .map(|signer| nonces.current(signer.address()))
+    };
+    // how many transactions each bundle has ahead of the calldata ones
+    let normal_txs = usize::from(params.normal_tx_signer.is_some());
+
+    let phase_start = Instant::now();
+    let mut bundle = next_bundle(
+        params,
+        &provider,
         block.gas_limit,
-        fill_pct,
-        *nonce,
-        chunk_size,
+        nonces.current(params.address),
+        normal_nonce(),
         tip_wei,
+        opts,
     )
     .await?;
+    if opts.exclude_reverting {
+        let head = block.number.unwrap_or_default();
+        bundle = exclude_reverting(
+            params,
+            &provider,
+            bundle,
+            simulation_block(head),
+            simulation_gas_limit(&block),
+            nonces.current(params.address),
+        )
+        .await?;
+    }
+    profile.record(profile::Phase::Construct, phase_start.elapsed());
+    if !opts.dry_sign {
+        let txs = bundle.transactions().len() * blocks_to_land;
+        check_value_balance(&provider, params.address, txs, opts).await?;
+    }
+    if opts.dry_sign {
+        print_signed(&replay::DumpedBundle::from_bundle(&bundle).transactions);
+        return Ok(summary::Summary::new(
+            "bundle", fill_pct, chunk_size, tip_wei,
+        ));
+    }
     // should always be 30 million:
     // tracing::debug!("block gas limit: {} gas", block.gas_limit);
 
     // on every block try to get the bundle in
     let mut block_sub = provider.watch_blocks().await?;
     tracing::info!("subscribed to blocks - waiting for next");
-    while block_sub.next().await.is_some() && landed <= blocks_to_land {
-        let block_number = provider.get_block_number().await?;
-        let block = provider
-            .get_block(BlockNumber::Latest)
-            .await?
+    let mut waiting_since = Instant::now();
+    if let Some(slot_seconds) = opts.slot_seconds {
+        if opts.slot_offset_ms >= slot_seconds * 1000 {
+            eyre::bail!(
+                "--slot-offset-ms {} is not within a {slot_seconds}s slot",
+                opts.slot_offset_ms
+            );
+        }
+    }
+    let mut paced_block = None;
+    // the highest head the block stream has shown, to spot it going backwards in a reorg
+    let mut highest_block: Option<U64> = None;
+    let mut spent = U256::zero();
+    let under_cap = |spent: U256| opts.max_fee_cap_eth.is_none_or(|cap| spent < cap);
+    let keep_going = |landed: usize, spent: U256| {
+        under_cap(spent)
+            && (opts.discover_min_tip
+                || match opts.measure_throughput {
+                    Some(secs) => started.elapsed() < Duration::from_secs(secs),
+                    None => landed <= blocks_to_land,
+                })
+    };
+    let mut landed_calldata_bytes = 0;
+    let mut landed_tx_sizes = Vec::new();
+    let mut value_sent = U256::zero();
+    let mut peak_bytes_per_sec: f64 = 0.0;
+    let mut summary = summary::Summary::new("bundle", fill_pct, chunk_size, tip_wei);
+    summary.label = opts.label.clone();
+    while block_sub.next().await.is_some() && keep_going(landed, spent) {
+        profile.record(profile::Phase::WaitForBlock, waiting_since.elapsed());
+        let block_number = or_next_block!(provider.get_block_number().await, opts);
+        let block = or_next_block!(provider.get_block(BlockNumber::Latest).await, opts)
             .expect("could not get latest block");
         //tracing::debug!("block gas limit: {} gas", block.gas_limit);
 
+        // the signed bundle's nonces may no longer line up with the chain
+        let mut reorged = false;
+        match highest_block {
+            Some(highest) if block_number < highest => {
+                reorged = true;
+                tracing::warn!(
+                    "reorg: head went back from block {highest} to {block_number}, resyncing nonces"
+                );
+                log_head_reorg(highest, block_number);
+                or_next_block!(
+                    nonces
+                        .resync(params.address, &provider, opts.nonce_block.into())
+                        .await,
+                    opts
+                );
+                if let Some(signer) = &params.normal_tx_signer {
+                    or_next_block!(
+                        nonces
+                            .resync(signer.address(), &provider, opts.nonce_block.into())
+                            .await,
+                        opts
+                    );
+                }
+            }
+            _ => highest_block = Some(block_number),
+        }
+
+        if let Some(target) = opts.target_slot {
+            ensure_target_ahead(target, block_number)?;
+            if block_number + 1 < U64::from(target) {
+                tracing::debug!("--target-slot {target}: at block {block_number}, waiting");
+                waiting_since = Instant::now();
+                continue;
+            }
+        }
+
+        if let (Some(max), Some(base_fee)) = (opts.max_base_fee_wei, block.base_fee_per_gas) {
+            if base_fee > U256::from(max) {
+                tracing::info!(
+                    "base fee too high, skipping block {block_number}: {base_fee} wei is over --max-base-fee-wei {max}"
+                );
+                waiting_since = Instant::now();
+                continue;
+            }
+        }
+
+        if let Some(slot_seconds) = opts.slot_seconds {
+            if paced_block == Some(block_number) {
+                tracing::debug!("already submitted for block {block_number}, skipping");
+                continue;
+            }
+            paced_block = Some(block_number);
+            let offset = Duration::from_millis(opts.slot_offset_ms);
+            pacing::wait_for_offset(block.timestamp.as_u64(), slot_seconds, offset).await;
+        }
+
         let span = tracing::trace_span!("submit-bundle", block = block_number.as_u64());
         let _enter = span.enter();
+        blocks_seen += 1;
+
+        // nonces already spent on chain mean (part of) this bundle landed in a block we
+        // didn't see it land in; resubmitting it would only fail, or count it twice.  after a
+        // reorg the nonces may have gone back instead, so the bundle is re-signed either way
+        let consumed = or_next_block!(
+            provider
+                .get_transaction_count(params.address, Some(BlockNumber::Latest.into()))
+                .await,
+            opts
+        );
+        let nonce = nonces.current(params.address);
+        if consumed > nonce || reorged {
+            if consumed > nonce {
+                tracing::info!(
+                    "nonces {}..{} already consumed, skipping",
+                    nonce,
+                    nonce + U256::from(bundle.transactions().len() - normal_txs)
+                );
+                nonces.set(params.address, consumed);
+            }
+            bundle = or_next_block!(
+                next_bundle(
+                    params,
+                    &provider,
+                    block.gas_limit,
+                    nonces.current(params.address),
+                    normal_nonce(),
+                    tip_wei,
+                    opts
+                )
+                .await,
+                opts
+            );
+            if opts.exclude_reverting {
+                bundle = or_next_block!(
+                    exclude_reverting(
+                        params,
+                        &provider,
+                        bundle.clone(),
+                        simulation_block(block_number),
+                        simulation_gas_limit(&block),
+                        nonces.current(params.address),
+                    )
+                    .await,
+                    opts
+                );
+            }
+        }
 
         let future_block_distance = 1; // 1 by default to get next block
-        let target_block = block_number + future_block_distance;
-        bundle = bundle
-            .set_block(target_block)
-            //.set_block(block_number + 1)
-            .set_simulation_block(block_number)
-            .set_simulation_timestamp(0);
+        let target_block = match opts.target_slot {
+            Some(target) => U64::from(target),
+            None => block_number + future_block_distance,
+        };
+        bundle = bundle.set_block(target_block); //.set_block(block_number + 1)
+        if !opts.no_simulation {
+            bundle = bundle
+                .set_simulation_block(simulation_block(block_number))
+                .set_simulation_timestamp(0);
+        }
 
         tracing::debug!(
             "bundle target block {:?}",
             target_block //block_number + FUTURE_BLOCK_DISTANCE
         );
 
-        let pending_bundle = provider.inner().send_bundle(&bundle).await?;
-        match pending_bundle.await {
+        if let Some(confirmations) = opts.reorg_confirmations {
+            while let Some(&(landed_block, tx_hash)) = unconfirmed.front() {
+                if block_number < landed_block + confirmations {
+                    break;
+                }
+                let canonical = match still_canonical(&provider, tx_hash, landed_block).await {
+                    Ok(canonical) => canonical,
+                    // leave it queued to be rechecked on the next block
+                    Err(err) if opts.continue_on_error => {
+                        tracing::error!("{err}, rechecking bundle landed in block {landed_block} on the next block");
+                        break;
+                    }
+                    Err(err) => return Err(err),
+                };
+                unconfirmed.pop_front();
+                if !canonical {
+                    landed -= 1;
+                    tracing::warn!(
+                        "reorg: bundle landed in block {landed_block} (tx {tx_hash:?}) is no longer canonical, {landed} landed"
+                    );
+                    log_reorg(landed_block, tx_hash, block_number);
+                }
+            }
+        }
+
+        if let Some(path) = &opts.dump_bundle {
+            replay::DumpedBundle::from_bundle(&bundle).write(path)?;
+        }
+
+        let parts = match opts.max_bundle_bytes {
+            Some(max_bytes) => bundle_builder::split_bundle(&bundle, max_bytes)
+                .into_iter()
+                .map(|part| {
+                    let part = part.set_block(target_block);
+                    if opts.no_simulation {
+                        part
+                    } else {
+                        part.set_simulation_block(simulation_block(block_number))
+                            .set_simulation_timestamp(0)
+                    }
+                })
+                .collect(),
+            None => vec![bundle.clone()],
+        };
+        if parts.len() > 1 {
+            tracing::info!(
+                "bundle of {} bytes split into {} sub-bundles",
+                bundle_builder::bundle_size_bytes(&bundle),
+                parts.len()
+            );
+        }
+
+        if !opts.target_builders.is_empty() {
+            tracing::info!(
+                "targeting builders {} for block {target_block}",
+                opts.target_builders.join(", ")
+            );
+        }
+        // a heuristic, see `inclusion::estimate`
+        let fill = (fill_pct / 100.0).min(1.0);
+        let inclusion_prob = match inclusion::estimate(&provider, U256::from(tip_wei), fill).await {
+            Ok(prob) => {
+                tracing::info!(
+                    "estimated inclusion probability at a {tip_wei} wei tip: {:.0}%",
+                    prob * 100.0
+                );
+                Some(prob)
+            }
+            Err(err) => {
+                tracing::debug!("couldn't estimate the inclusion probability: {err}");
+                None
+            }
+        };
+        let phase_start = Instant::now();
+        // every part goes to every relay at once, `--max-concurrent-bundles` at a time
+        let slots = &bundle_slots;
+        let primary = futures::future::join_all(
+            parts
+                .iter()
+                .map(|part| send_throttled(slots, provider.inner(), part, opts)),
+        );
+        let extra = futures::future::join_all(parts.iter().flat_map(|part| {
+            extra_relays
+                .iter()
+                .zip(&relays[1..])
+                .map(move |(relay, entry)| async move {
+                    (entry, send_throttled(slots, relay, part, opts).await)
+                })
+        }));
+        let (primary, extra) = futures::future::join(primary, extra).await;
+        for (entry, sent) in extra {
+            if let Err(err) = sent {
+                tracing::warn!("sending to relay {} failed: {err}", entry.url);
+            }
+        }
+        // one failed part means the bundle can't land as a whole, so give up on this block
+        let pending_bundles =
+            or_next_block!(primary.into_iter().collect::<Result<Vec<_>, _>>(), opts);
+        let Some(first_tx) = pending_bundles
+            .first()
+            .and_then(|pending| pending.transactions.first())
+            .copied()
+        else {
+            tracing::warn!(
+                "the relay returned no transactions for the bundle, continuing with the next block"
+            );
+            continue;
+        };
+        let relay_latency = phase_start.elapsed();
+        let submitted_at = phase_start;
+        profile.record(profile::Phase::Submit, relay_latency);
+        attempted += 1;
+        metrics::record_attempt();
+        // `--discover-min-tip` may raise `tip_wei` once this attempt is judged
+        let sent_tip = tip_wei;
+        let phase_start = Instant::now();
+        // the nonce we'll be at if this bundle lands, for building the next one ahead of time
+        let landed_nonce =
+            nonces.current(params.address) + U256::from(bundle.transactions().len() - normal_txs);
+        let inclusion = futures::future::join_all(
+            pending_bundles
+                .into_iter()
+                .map(|pending| await_inclusion(&provider, pending, opts)),
+        );
+        let (outcomes, prebuilt) = if opts.prebuild {
+            let prebuild = next_bundle(
+                params,
+                &provider,
+                block.gas_limit,
+                landed_nonce,
+                normal_nonce().map(|nonce| nonce + 1),
+                tip_wei,
+                opts,
+            );
+            let (outcomes, prebuilt) = futures::future::join(inclusion, prebuild).await;
+            (outcomes, Some(prebuilt))
+        } else {
+            (inclusion.await, None)
+        };
+        // only counts as landed if every sub-bundle made it in
+        let outcome = outcomes
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()
+            .map(|hashes| hashes[0]);
+        profile.record(profile::Phase::Inclusion, phase_start.elapsed());
+        let inclusion_latency = submitted_at.elapsed();
+        let bundle_landed = outcome.is_ok();
+        match outcome {
             Ok(bundle_hash) => {
                 // TODO: Can we log more info from the Flashbots API?
                 tracing::info!("bundle #{} included! hash: {:?}", landed, bundle_hash);
+                if opts.reorg_confirmations.is_some() {
+                    unconfirmed.push_back((target_block, first_tx));
+                }
 
                 landed += 1; // actually check if we landed it?
-                log_attempt(chunk_size, tip_wei, fill_pct, true, block_number);
+                metrics::record_landing(target_block.as_u64(), tip_wei, inclusion_latency);
+                // the nonce isn't resynced until after this attempt, so it's still the bundle's first
+                let first_nonce = nonces.current(params.address);
+                let mut bundle_gas = U256::zero();
+                let hashes = bundle.transaction_hashes();
+                let receipts = match futures::future::try_join_all(
+                    hashes
+                        .iter()
+                        .map(|hash| provider.get_transaction_receipt(*hash)),
+                )
+                .await
+                {
+                    Ok(receipts) => receipts,
+                    // the bundle still landed, only what it cost goes uncounted
+                    Err(err) if opts.continue_on_error => {
+                        tracing::error!("{err}, the landed bundle's gas and spend aren't counted");
+                        vec![None; hashes.len()]
+                    }
+                    Err(err) => return Err(err.into()),
+                };
+                for (i, (hash, receipt)) in hashes.into_iter().zip(receipts).enumerate() {
+                    if i < normal_txs {
+                        match &receipt {
+                            Some(receipt) => tracing::info!(
+                                "normal transfer {hash:?} landed alongside the bundle in block {}",
+                                receipt.block_number.unwrap_or(target_block)
+                            ),
+                            None => tracing::warn!(
+                                "normal transfer {hash:?} did not land alongside the bundle"
+                            ),
+                        }
+                    }
+                    if let Some(receipt) = receipt {
+                        spent += receipt_cost(&receipt);
+                        if i < normal_txs {
+                            continue;
+                        }
+                        bundle_gas += receipt.gas_used.unwrap_or_default();
+                        if let Some(path) = &opts.export_landed_txs {
+                            let block = receipt.block_number.unwrap_or(target_block);
+                            let nonce = first_nonce + U256::from(i - normal_txs);
+                            export_landed_tx(path, hash, block, nonce)?;
+                        }
+                    }
+                }
+                value_sent += bundle_builder::bundle_value(&bundle)?;
+                let calldata_bytes = bundle_builder::bundle_calldata_bytes(&bundle)?;
+                landed_calldata_bytes += calldata_bytes;
+                if opts.size_distribution.is_some() || opts.replay_distribution.is_some() {
+                    landed_tx_sizes.extend(bundle_builder::bundle_tx_data_sizes(&bundle)?);
+                }
+                if opts.analyze_blocks {
+                    let included = match provider.get_block_with_txs(target_block).await {
+                        Ok(included) => included,
+                        // the bundle still landed, only its block share goes unreported
+                        Err(err) if opts.continue_on_error => {
+                            tracing::error!(
+                                "{err}, block {target_block}'s share of calldata and gas isn't counted"
+                            );
+                            None
+                        }
+                        Err(err) => return Err(err.into()),
+                    };
+                    if let Some(included) = included {
+                        let block_calldata: usize =
+                            included.transactions.iter().map(|tx| tx.input.len()).sum();
+                        tracing::info!(
+                            "block {target_block}: our {calldata_bytes} of {block_calldata} calldata bytes, {bundle_gas} of {} gas",
+                            included.gas_used
+                        );
+                        summary.add_block_share(
+                            calldata_bytes,
+                            bundle_gas.as_u64(),
+                            block_calldata,
+                            included.gas_used.as_u64(),
+                        );
+                    }
+                }
+                if opts.measure_throughput.is_some() {
+                    let included = match provider.get_block(target_block).await {
+                        Ok(included) => included,
+                        // the bundle still landed, only its throughput sample is lost
+                        Err(err) if opts.continue_on_error => {
+                            tracing::error!(
+                                "{err}, block {target_block} isn't sampled for peak throughput"
+                            );
+                            None
+                        }
+                        Err(err) => return Err(err.into()),
+                    };
+                    if let Some(included) = included {
+                        let block_time = included.timestamp.saturating_sub(block.timestamp);
+                        if !block_time.is_zero() {
+                            let bytes_per_sec = calldata_bytes as f64 / block_time.as_u64() as f64;
+                            peak_bytes_per_sec = peak_bytes_per_sec.max(bytes_per_sec);
+                        }
+                    }
+                }
+                tracing::info!(
+                    "relay acknowledged in {}ms, included after {}ms",
+                    relay_latency.as_millis(),
+                    inclusion_latency.as_millis()
+                );
+                let latency = AttemptLatency {
+                    relay: relay_latency,
+                    inclusion: Some(inclusion_latency),
+                };
+                log_attempt(
+                    chunk_size,
+                    tip_wei,
+                    fill_pct,
+                    true,
+                    block_number,
+                    latency,
+                    inclusion_prob,
+                );
+                if opts.discover_min_tip {
+                    landed_streak += 1;
+                    if landed_streak >= opts.discover_streak {
+                        tracing::info!(
+                            "--discover-min-tip: {landed_streak} bundles in a row landed tipping {tip_wei} wei"
+                        );
+                        discovered_min_tip = Some(tip_wei);
+                    }
+                }
+            }
+            Err(err @ PendingBundleError::ProviderError(_)) if !opts.continue_on_error => {
+                return Err(err.into());
             }
             Err(err) => {
                 tracing::error!("{}. did not land bundle, retrying.", err);
-                log_attempt(chunk_size, tip_wei, fill_pct, false, block_number);
+                let latency = AttemptLatency {
+                    relay: relay_latency,
+                    inclusion: None,
+                };
+                log_attempt(
+                    chunk_size,
+                    tip_wei,
+                    fill_pct,
+                    false,
+                    block_number,
+                    latency,
+                    inclusion_prob,
+                );
+                if opts.discover_min_tip {
+                    landed_streak = 0;
+                    tip_wei += opts.discover_step_wei;
+                    tracing::info!("--discover-min-tip: raising the tip to {tip_wei} wei");
+                }
+            }
+        }
+        // a kept bundle still has the nonces it was signed with
+        let keep_bundle = opts.keep_bundle_until_landed && !bundle_landed;
+        if !keep_bundle {
+            or_next_block!(
+                nonces
+                    .resync(params.address, &provider, opts.nonce_block.into())
+                    .await,
+                opts
+            ); // TODO: keep track of nonce ourselves?
+            if let Some(signer) = &params.normal_tx_signer {
+                or_next_block!(
+                    nonces
+                        .resync(signer.address(), &provider, opts.nonce_block.into())
+                        .await,
+                    opts
+                );
             }
         }
-        *nonce = provider
-            .get_transaction_count(address, Some(BlockNumber::Pending.into()))
-            .await?; // TODO: keep track of nonce ourselves?
 
-        tracing::debug!("signing new bundle for next block (new nonce: {})", nonce);
-        bundle = bundle_builder::construct_bundle(
-            chain_id,
-            address,
-            receiver,
-            provider.clone(),
-            block.gas_limit,
-            fill_pct,
-            *nonce,
-            chunk_size,
+        progress.update(
+            block_number.as_u64(),
+            landed,
+            attempted,
             tip_wei,
-        )
-        .await?;
+            nonces.current(params.address).as_u64(),
+        );
+        if let Some(path) = &opts.timeseries_csv {
+            let block_bytes = bundle_builder::block_data_bytes(block.gas_limit, params.fork);
+            let fill =
+                bundle_builder::bundle_calldata_bytes(&bundle)? as f64 * 100.0 / block_bytes as f64;
+            append_timeseries_row(path, &block, attempted, landed, sent_tip, fill)?;
+        }
+        summary.blocks = blocks_seen;
+        summary.spent_eth = wei_to_eth(spent)?;
+        summary.value_sent_eth = wei_to_eth(value_sent)?;
+        summary.end_nonce = nonces.current(params.address).as_u64();
+        summary.landed_calldata_bytes = landed_calldata_bytes;
+        if let Some(distribution) = &opts.size_distribution {
+            summary.size_distribution = distribution.describe(&landed_tx_sizes);
+        }
+        if let Some(replay) = &opts.replay_distribution {
+            summary.size_distribution = replay.describe(&landed_tx_sizes);
+        }
+        summary.finish(landed, attempted, started.elapsed());
+        summary::record_partial(&summary, block_number.as_u64());
+
+        if let Some(every) = opts.checkpoint_every {
+            if blocks_seen.is_multiple_of(every) {
+                let elapsed = started.elapsed();
+                tracing::info!(
+                    "checkpoint after {blocks_seen} blocks: landed {landed}/{attempted} ({:.1}%), elapsed {}s, tip {tip_wei} wei",
+                    progress::success_rate(landed, attempted),
+                    elapsed.as_secs(),
+                );
+                log_checkpoint(blocks_seen, landed, attempted, elapsed, tip_wei);
+            }
+        }
+
+        if let Some(every) = opts.balance_check_every {
+            if blocks_seen.is_multiple_of(every)
+                && !or_next_block!(balances_ok(&provider, &signers, opts).await, opts)
+                && opts.stop_on_low_balance
+            {
+                tracing::warn!("stopping submission, a signer is below --low-balance-eth");
+                break;
+            }
+        }
+
+        if discovered_min_tip.is_some() || opts.target_slot.is_some() {
+            break;
+        }
+        if keep_bundle {
+            kept_for += 1;
+            tracing::debug!(
+                "resubmitting the same signed bundle (nonce {}) next block",
+                nonces.current(params.address)
+            );
+            waiting_since = Instant::now();
+            continue;
+        }
+        if opts.keep_bundle_until_landed && bundle_landed {
+            tracing::info!("the signed bundle landed after {} blocks", kept_for + 1);
+            kept_for = 0;
+        }
+
+        tracing::debug!(
+            "signing new bundle for next block (new nonce: {})",
+            nonces.current(params.address)
+        );
+        let phase_start = Instant::now();
+        bundle = match prebuilt {
+            Some(Ok(prebuilt)) if nonces.current(params.address) == landed_nonce => {
+                tracing::debug!("using the bundle prebuilt from nonce {landed_nonce}");
+                prebuilt
+            }
+            Some(Err(err)) => {
+                tracing::debug!("prebuilding the next bundle failed ({err}), building it now");
+                or_next_block!(
+                    next_bundle(
+                        params,
+                        &provider,
+                        block.gas_limit,
+                        nonces.current(params.address),
+                        normal_nonce(),
+                        tip_wei,
+                        opts
+                    )
+                    .await,
+                    opts
+                )
+            }
+            _ => or_next_block!(
+                next_bundle(
+                    params,
+                    &provider,
+                    block.gas_limit,
+                    nonces.current(params.address),
+                    normal_nonce(),
+                    tip_wei,
+                    opts
+                )
+                .await,
+                opts
+            ),
+        };
+        if opts.exclude_reverting {
+            bundle = or_next_block!(
+                exclude_reverting(
+                    params,
+                    &provider,
+                    bundle.clone(),
+                    simulation_block(block_number),
+                    simulation_gas_limit(&block),
+                    nonces.current(params.address),
+                )
+                .await,
+                opts
+            );
+        }
+        profile.record(profile::Phase::Construct, phase_start.elapsed());
+        waiting_since = Instant::now();
+    }
+    progress.finish();
+    if opts.profile {
+        eprint!("{}", profile.report());
     }
 
     tracing::debug!("Done! End Block: {}", provider.get_block_number().await?);
 
-    Ok(())
+    if !under_cap(spent) {
+        tracing::warn!(
+            "--max-fee-cap-eth reached after spending {} ETH, stopping",
+            ethers::utils::format_units(spent, "ether")?
+        );
+    }
+    // bundles that landed too close to the end to reach `--reorg-confirmations`: drop the
+    // ones that are already orphaned, and count the rest as tentative
+    if !unconfirmed.is_empty() {
+        let head = provider.get_block_number().await?;
+        for (landed_block, tx_hash) in unconfirmed.drain(..) {
+            match still_canonical(&provider, tx_hash, landed_block).await {
+                Ok(true) => summary.tentative += 1,
+                Ok(false) => {
+                    landed -= 1;
+                    tracing::warn!(
+                        "reorg: bundle landed in block {landed_block} (tx {tx_hash:?}) is no longer canonical, {landed} landed"
+                    );
+                    log_reorg(landed_block, tx_hash, head);
+                }
+                Err(err) => {
+                    tracing::error!(
+                        "{err}, counting bundle landed in block {landed_block} as tentative"
+                    );
+                    summary.tentative += 1;
+                }
+            }
+        }
+    }
+    summary.blocks = blocks_seen;
+    summary.spent_eth = wei_to_eth(spent)?;
+    summary.value_sent_eth = wei_to_eth(value_sent)?;
+    summary.end_nonce = nonces.current(params.address).as_u64();
+    summary.landed_calldata_bytes = landed_calldata_bytes;
+    if let Some(distribution) = &opts.size_distribution {
+        summary.size_distribution = distribution.describe(&landed_tx_sizes);
+    }
+    if let Some(replay) = &opts.replay_distribution {
+        summary.size_distribution = replay.describe(&landed_tx_sizes);
+    }
+    summary.peak_bytes_per_sec = peak_bytes_per_sec;
+    summary.tip_wei = tip_wei;
+    summary.discovered_min_tip_wei = discovered_min_tip;
+    summary.finish(landed, attempted, started.elapsed());
+    Ok(summary)
 }
@@ -0,0 +1,172 @@
+use std::fmt::Write as _;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// upper bounds of the inclusion latency histogram buckets, in seconds
+const LATENCY_BUCKETS: [f64; 9] = [1.0, 2.0, 4.0, 8.0, 12.0, 16.0, 24.0, 32.0, 64.0];
+
+/// the landing that last fell into a histogram bucket, to correlate it with a block
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Exemplar {
+    block: u64,
+    tip_wei: u64,
+    latency_secs: f64,
+}
+
+#[derive(Debug, Default)]
+struct Metrics {
+    attempted: u64,
+    landed: u64,
+    latency_sum: f64,
+    /// landings per bucket, not cumulative; the last one is `+Inf`
+    bucket_counts: [u64; LATENCY_BUCKETS.len() + 1],
+    exemplars: [Option<Exemplar>; LATENCY_BUCKETS.len() + 1],
+}
+
+static METRICS: Mutex<Metrics> = Mutex::new(Metrics {
+    attempted: 0,
+    landed: 0,
+    latency_sum: 0.0,
+    bucket_counts: [0; LATENCY_BUCKETS.len() + 1],
+    exemplars: [None; LATENCY_BUCKETS.len() + 1],
+});
+
+fn metrics() -> std::sync::MutexGuard<'static, Metrics> {
+    METRICS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// count a submitted bundle
+pub fn record_attempt() {
+    metrics().attempted += 1;
+}
+
+/// count a landed bundle, keeping it as the exemplar of its latency bucket
+pub fn record_landing(block: u64, tip_wei: u64, latency: Duration) {
+    metrics().observe(Exemplar {
+        block,
+        tip_wei,
+        latency_secs: latency.as_secs_f64(),
+    });
+}
+
+impl Metrics {
+    fn observe(&mut self, exemplar: Exemplar) {
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|le| exemplar.latency_secs <= *le)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        self.landed += 1;
+        self.latency_sum += exemplar.latency_secs;
+        self.bucket_counts[bucket] += 1;
+        self.exemplars[bucket] = Some(exemplar);
+    }
+
+    /// the OpenMetrics text exposition, with an exemplar on every bucket that has one
+    fn render(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# TYPE stress4844_bundles_attempted counter");
+        let _ = writeln!(out, "stress4844_bundles_attempted_total {}", self.attempted);
+        let _ = writeln!(out, "# TYPE stress4844_bundles_landed counter");
+        let _ = writeln!(out, "stress4844_bundles_landed_total {}", self.landed);
+        let _ = writeln!(out, "# TYPE stress4844_inclusion_latency_seconds histogram");
+        let _ = writeln!(out, "# UNIT stress4844_inclusion_latency_seconds seconds");
+        let mut cumulative = 0;
+        for (i, count) in self.bucket_counts.iter().enumerate() {
+            cumulative += count;
+            let le = match LATENCY_BUCKETS.get(i) {
+                Some(le) => format!("{le:.1}"),
+                None => "+Inf".to_string(),
+            };
+            let _ = write!(
+                out,
+                "stress4844_inclusion_latency_seconds_bucket{{le=\"{le}\"}} {cumulative}"
+            );
+            if let Some(exemplar) = self.exemplars[i] {
+                let _ = write!(
+                    out,
+                    " # {{block=\"{}\",tip_wei=\"{}\"}} {}",
+                    exemplar.block, exemplar.tip_wei, exemplar.latency_secs
+                );
+            }
+            out.push('\n');
+        }
+        let _ = writeln!(
+            out,
+            "stress4844_inclusion_latency_seconds_count {}",
+            self.landed
+        );
+        let _ = writeln!(
+            out,
+            "stress4844_inclusion_latency_seconds_sum {}",
+            self.latency_sum
+        );
+        out.push_str("# EOF\n");
+        out
+    }
+}
+
+/// serve the metrics on `addr` until the run ends, answering every request with them
+pub async fn serve(addr: SocketAddr) -> eyre::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    tracing::info!("serving OpenMetrics on http://{addr}/metrics");
+    tokio::spawn(async move {
+        loop {
+            let Ok((mut stream, _)) = listener.accept().await else {
+                continue;
+            };
+            tokio::spawn(async move {
+                // the request itself doesn't matter, there's only the one page
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request).await;
+                let body = metrics().render();
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-type: application/openmetrics-text; version=1.0.0; charset=utf-8\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                    body.len()
+                );
+                if let Err(err) = stream.write_all(response.as_bytes()).await {
+                    tracing::debug!("could not send metrics: {err}");
+                }
+            });
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_are_cumulative_with_exemplars() {
+        let mut metrics = Metrics {
+            attempted: 3,
+            ..Default::default()
+        };
+        metrics.observe(Exemplar {
+            block: 100,
+            tip_wei: 5,
+            latency_secs: 1.5,
+        });
+        metrics.observe(Exemplar {
+            block: 101,
+            tip_wei: 6,
+            latency_secs: 100.0,
+        });
+        let text = metrics.render();
+        assert!(text.contains("stress4844_bundles_attempted_total 3\n"));
+        assert!(text.contains("stress4844_bundles_landed_total 2\n"));
+        assert!(text.contains("stress4844_inclusion_latency_seconds_bucket{le=\"1.0\"} 0\n"));
+        assert!(text.contains(
+            "stress4844_inclusion_latency_seconds_bucket{le=\"2.0\"} 1 # {block=\"100\",tip_wei=\"5\"} 1.5\n"
+        ));
+        assert!(text.contains(
+            "stress4844_inclusion_latency_seconds_bucket{le=\"+Inf\"} 2 # {block=\"101\",tip_wei=\"6\"} 100\n"
+        ));
+        assert!(text.ends_with("# EOF\n"));
+    }
+}
@@ -0,0 +1,122 @@
+use ethers::prelude::*;
+use ethers_flashbots::{BundleRequest, FlashbotsMiddlewareError, PendingBundle};
+use serde::Serialize;
+use std::sync::Arc;
+
+use crate::bundle_builder::{raw_transactions, reverting_tx_hashes};
+
+/// what a MEV-Share bundle lets searchers see about its transactions
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Hint {
+    Calldata,
+    ContractAddress,
+    Logs,
+    FunctionSelector,
+    Hash,
+    TxHash,
+}
+
+/// `mev_sendBundle` parameters, see
+/// <https://docs.flashbots.net/flashbots-mev-share/searchers/understanding-bundles>
+#[derive(Serialize)]
+struct MevShareBundle<'a> {
+    version: &'static str,
+    inclusion: Inclusion,
+    body: Vec<BodyTx>,
+    privacy: Privacy<'a>,
+}
+
+#[derive(Serialize)]
+struct Inclusion {
+    block: U64,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BodyTx {
+    tx: Bytes,
+    can_revert: bool,
+}
+
+#[derive(Serialize)]
+struct Privacy<'a> {
+    hints: &'a [Hint],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    builders: &'a [String],
+}
+
+/// `send_bundle`, but as a MEV-Share bundle revealing `hints`, and only to `builders` if
+/// there are any.  only the bundle's `revertingTxHashes` may revert.
+pub async fn send<'a>(
+    relay: &'a crate::Relay,
+    bundle: &BundleRequest,
+    hints: &[Hint],
+    builders: &[String],
+) -> Result<PendingBundle<'a, Http>, FlashbotsMiddlewareError<Arc<Provider<Http>>, LocalWallet>> {
+    let block = bundle
+        .block()
+        .ok_or(FlashbotsMiddlewareError::MissingParameters)?;
+    let reverting = reverting_tx_hashes(bundle);
+    let body = raw_transactions(bundle)
+        .into_iter()
+        .zip(bundle.transaction_hashes())
+        .map(|(tx, hash)| BodyTx {
+            tx,
+            can_revert: reverting.contains(&hash),
+        })
+        .collect();
+    let request = MevShareBundle {
+        version: "v0.1",
+        inclusion: Inclusion { block },
+        body,
+        privacy: Privacy { hints, builders },
+    };
+    let response: crate::SendBundleResponse = relay
+        .relay()
+        .request("mev_sendBundle", [request])
+        .await
+        .map_err(FlashbotsMiddlewareError::RelayError)?;
+    tracing::info!(
+        "mev-share node acknowledged bundle {:?} for block {block}",
+        response.bundle_hash
+    );
+    Ok(PendingBundle::new(
+        response.bundle_hash,
+        block,
+        bundle.transaction_hashes(),
+        relay.provider(),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_matches_mev_send_bundle() {
+        let request = MevShareBundle {
+            version: "v0.1",
+            inclusion: Inclusion {
+                block: U64::from(16),
+            },
+            body: vec![BodyTx {
+                tx: Bytes::from(vec![0x02, 0xf8]),
+                can_revert: false,
+            }],
+            privacy: Privacy {
+                hints: &[Hint::Hash, Hint::ContractAddress],
+                builders: &[],
+            },
+        };
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            serde_json::json!({
+                "version": "v0.1",
+                "inclusion": {"block": "0x10"},
+                "body": [{"tx": "0x02f8", "canRevert": false}],
+                "privacy": {"hints": ["hash", "contract_address"]},
+            })
+        );
+    }
+}
@@ -0,0 +1,123 @@
+use ethers::prelude::*;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+use crate::error::{Result, StressError};
+
+/// the next nonce of every address we sign for, shared by everything signing for it.  handing
+/// out nonces only takes a read lock, so concurrent signers get a gapless sequence between them.
+#[derive(Debug, Default)]
+pub struct NonceManager {
+    nonces: RwLock<HashMap<Address, AtomicU64>>,
+}
+
+impl NonceManager {
+    /// the nonce `next` would hand out for `address`, without taking it
+    pub fn current(&self, address: Address) -> U256 {
+        let nonces = self.nonces.read().expect("nonce lock poisoned");
+        U256::from(
+            nonces
+                .get(&address)
+                .map_or(0, |nonce| nonce.load(Ordering::SeqCst)),
+        )
+    }
+
+    /// take the next nonce of `address`
+    pub fn next(&self, address: Address) -> U256 {
+        self.reserve(address, 1)
+    }
+
+    /// take `count` consecutive nonces of `address`, returning the first
+    pub fn reserve(&self, address: Address, count: u64) -> U256 {
+        {
+            let nonces = self.nonces.read().expect("nonce lock poisoned");
+            if let Some(nonce) = nonces.get(&address) {
+                return U256::from(nonce.fetch_add(count, Ordering::SeqCst));
+            }
+        }
+        let mut nonces = self.nonces.write().expect("nonce lock poisoned");
+        U256::from(
+            nonces
+                .entry(address)
+                .or_default()
+                .fetch_add(count, Ordering::SeqCst),
+        )
+    }
+
+    /// hand out `address`'s nonces from `nonce` on, e.g. to give back ones that were never sent
+    pub fn set(&self, address: Address, nonce: U256) {
+        let mut nonces = self.nonces.write().expect("nonce lock poisoned");
+        nonces.insert(address, AtomicU64::new(nonce.as_u64()));
+    }
+
+    /// start over from `address`'s transaction count at `block`, returning it
+    pub async fn resync<M: Middleware>(
+        &self,
+        address: Address,
+        provider: &M,
+        block: BlockNumber,
+    ) -> Result<U256>
+    where
+        M::Error: 'static,
+    {
+        let nonce = provider
+            .get_transaction_count(address, Some(block.into()))
+            .await
+            .map_err(StressError::rpc)?;
+        self.set(address, nonce);
+        Ok(nonce)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn concurrent_next_is_gapless() {
+        let nonces = NonceManager::default();
+        let (a, b) = (Address::repeat_byte(0xaa), Address::repeat_byte(0xbb));
+        nonces.set(a, U256::from(10));
+
+        let taken: Vec<(Address, U256)> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..8)
+                .map(|worker| {
+                    let nonces = &nonces;
+                    scope.spawn(move || {
+                        let address = if worker % 2 == 0 { a } else { b };
+                        (0..100)
+                            .map(|_| (address, nonces.next(address)))
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .flat_map(|worker| worker.join().unwrap())
+                .collect()
+        });
+
+        for (address, start) in [(a, 10), (b, 0)] {
+            let got: BTreeSet<u64> = taken
+                .iter()
+                .filter(|(taken_by, _)| *taken_by == address)
+                .map(|(_, nonce)| nonce.as_u64())
+                .collect();
+            assert_eq!(got, (start..start + 400).collect());
+            assert_eq!(nonces.current(address), U256::from(start + 400));
+        }
+    }
+
+    #[test]
+    fn reserve_takes_a_block_of_nonces() {
+        let nonces = NonceManager::default();
+        let address = Address::repeat_byte(0x48);
+        nonces.set(address, U256::from(5));
+        assert_eq!(nonces.reserve(address, 3), U256::from(5));
+        assert_eq!(nonces.next(address), U256::from(8));
+        nonces.set(address, U256::from(8));
+        assert_eq!(nonces.current(address), U256::from(8));
+    }
+}
@@ -0,0 +1,50 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// how long to wait from `now` (since the unix epoch) until `offset` into the slot that
+/// started with a block at `block_timestamp`.  `None` if that moment has already passed.
+pub fn slot_delay(block_timestamp: u64, now: Duration, offset: Duration) -> Option<Duration> {
+    let target = Duration::from_secs(block_timestamp) + offset;
+    target.checked_sub(now).filter(|delay| !delay.is_zero())
+}
+
+/// sleep until `offset` into the slot of the block at `block_timestamp`, warning when it's
+/// already so late that the next block is due
+pub async fn wait_for_offset(block_timestamp: u64, slot_seconds: u64, offset: Duration) {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    match slot_delay(block_timestamp, now, offset) {
+        Some(delay) => {
+            tracing::debug!(
+                "waiting {}ms to submit at the slot offset",
+                delay.as_millis()
+            );
+            tokio::time::sleep(delay).await;
+        }
+        None => {
+            let next_slot = Duration::from_secs(block_timestamp + slot_seconds);
+            if now >= next_slot {
+                tracing::warn!(
+                    "block at {block_timestamp} arrived {}ms after its {slot_seconds}s slot ended, submitting late",
+                    (now - next_slot).as_millis()
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waits_until_the_offset_into_the_slot() {
+        let offset = Duration::from_secs(2);
+        assert_eq!(
+            slot_delay(1_000, Duration::from_millis(1_000_500), offset),
+            Some(Duration::from_millis(1_500))
+        );
+        assert_eq!(slot_delay(1_000, Duration::from_secs(1_002), offset), None);
+        assert_eq!(slot_delay(1_000, Duration::from_secs(1_005), offset), None);
+    }
+}
@@ -0,0 +1,127 @@
+use eyre::{Result, WrapErr};
+use std::path::Path;
+
+/// one piece of a `--payload-pattern-file` template
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    /// these exact bytes
+    Fixed(Vec<u8>),
+    /// this many random bytes, drawn fresh for every transaction
+    Random(usize),
+}
+
+/// a calldata template, tiled over each transaction's payload instead of random bytes.
+///
+/// the file is a list of whitespace separated tokens, with `#` starting a comment:
+/// - hex bytes, with or without `0x`, e.g. `0x6080` or `ff 00`, are copied as they are
+/// - `*N` is a run of `N` random bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    segments: Vec<Segment>,
+}
+
+impl Pattern {
+    pub fn load(path: &Path) -> Result<Self> {
+        let spec = std::fs::read_to_string(path)?;
+        Self::parse(&spec).wrap_err_with(|| format!("invalid payload pattern {}", path.display()))
+    }
+
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut segments = Vec::new();
+        for (line_no, line) in spec.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default();
+            for token in line.split_whitespace() {
+                let segment = match token.strip_prefix('*') {
+                    Some(len) => match len.parse::<usize>() {
+                        Ok(len) if len > 0 => Segment::Random(len),
+                        _ => eyre::bail!(
+                            "line {}: `{token}` should be `*N` with N a positive number of bytes",
+                            line_no + 1
+                        ),
+                    },
+                    None => {
+                        let hex = token.strip_prefix("0x").unwrap_or(token);
+                        let bytes = ethers::utils::hex::decode(hex).map_err(|err| {
+                            eyre::eyre!("line {}: `{token}` is not hex bytes: {err}", line_no + 1)
+                        })?;
+                        if bytes.is_empty() {
+                            eyre::bail!("line {}: `{token}` has no bytes", line_no + 1);
+                        }
+                        Segment::Fixed(bytes)
+                    }
+                };
+                segments.push(segment);
+            }
+        }
+        if segments.is_empty() {
+            eyre::bail!("pattern has no bytes");
+        }
+        Ok(Self { segments })
+    }
+
+    /// bytes in one repetition of the pattern
+    pub fn len(&self) -> usize {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Fixed(bytes) => bytes.len(),
+                Segment::Random(len) => *len,
+            })
+            .sum()
+    }
+
+    /// repeat the pattern until it's `size` bytes long, cutting the last repetition short
+    pub fn fill(&self, size: usize) -> Vec<u8> {
+        let mut data = Vec::with_capacity(size);
+        'tiling: loop {
+            for segment in &self.segments {
+                let left = size - data.len();
+                if left == 0 {
+                    break 'tiling;
+                }
+                match segment {
+                    Segment::Fixed(bytes) => {
+                        data.extend_from_slice(&bytes[..bytes.len().min(left)]);
+                    }
+                    Segment::Random(len) => {
+                        data.extend((0..(*len).min(left)).map(|_| fastrand::u8(..)));
+                    }
+                }
+            }
+        }
+        data
+    }
+}
+
+impl crate::payload::PayloadGenerator for Pattern {
+    fn generate(&self, size: usize) -> Vec<u8> {
+        self.fill(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiles_fixed_and_random_regions() {
+        let pattern = Pattern::parse("0xdead # header\n*2\nbe").unwrap();
+        assert_eq!(pattern.len(), 5);
+        let data = pattern.fill(12);
+        assert_eq!(data.len(), 12);
+        assert_eq!(&data[0..2], &[0xde, 0xad]);
+        assert_eq!(data[4], 0xbe);
+        assert_eq!(&data[5..7], &[0xde, 0xad]);
+        assert_eq!(data[9], 0xbe);
+        assert_eq!(&data[10..12], &[0xde, 0xad]);
+    }
+
+    #[test]
+    fn rejects_bad_tokens() {
+        assert!(Pattern::parse("# only a comment").is_err());
+        assert!(Pattern::parse("*0").is_err());
+        assert!(Pattern::parse("*x").is_err());
+        assert!(Pattern::parse("abc").is_err());
+        assert!(Pattern::parse("0x").is_err());
+    }
+}
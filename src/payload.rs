@@ -0,0 +1,118 @@
+use ethers::core::rand::{distributions::Standard, Rng};
+use serde::Serialize;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// makes the calldata of each transaction.  a new kind of payload only has to implement this
+/// and get a `DataFill` variant (or come from somewhere else, like `--payload-pattern-file`).
+pub trait PayloadGenerator: Debug + Send + Sync {
+    /// `size` bytes of calldata
+    fn generate(&self, size: usize) -> Vec<u8>;
+}
+
+/// the built-in payloads, picked with `--data-fill`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DataFill {
+    /// uniformly random bytes, nearly all non-zero
+    Random,
+    /// all zero bytes, the cheapest calldata per byte
+    Zeros,
+    /// back to back ERC-20 `transfer(address,uint256)` calls to random addresses
+    Erc20,
+    /// one random 32 byte word repeated: pays full calldata gas but compresses to almost nothing
+    Compressible,
+}
+
+impl DataFill {
+    pub fn generator(self) -> Arc<dyn PayloadGenerator> {
+        match self {
+            DataFill::Random => Arc::new(Random),
+            DataFill::Zeros => Arc::new(Zeros),
+            DataFill::Erc20 => Arc::new(Erc20Transfers),
+            DataFill::Compressible => Arc::new(Compressible),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Random;
+
+impl PayloadGenerator for Random {
+    fn generate(&self, size: usize) -> Vec<u8> {
+        ethers::core::rand::thread_rng()
+            .sample_iter(Standard)
+            .take(size)
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub struct Zeros;
+
+impl PayloadGenerator for Zeros {
+    fn generate(&self, size: usize) -> Vec<u8> {
+        vec![0; size]
+    }
+}
+
+/// `transfer(address,uint256)`
+const ERC20_TRANSFER_SELECTOR: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb];
+
+#[derive(Debug)]
+pub struct Erc20Transfers;
+
+impl PayloadGenerator for Erc20Transfers {
+    fn generate(&self, size: usize) -> Vec<u8> {
+        let mut data = Vec::with_capacity(size + 68);
+        while data.len() < size {
+            data.extend_from_slice(&ERC20_TRANSFER_SELECTOR);
+            // left-padded address, then an amount that fits in 8 bytes like most real ones
+            data.extend_from_slice(&[0; 12]);
+            data.extend((0..20).map(|_| fastrand::u8(..)));
+            data.extend_from_slice(&[0; 24]);
+            data.extend_from_slice(&fastrand::u64(..).to_be_bytes());
+        }
+        data.truncate(size);
+        data
+    }
+}
+
+#[derive(Debug)]
+pub struct Compressible;
+
+impl PayloadGenerator for Compressible {
+    fn generate(&self, size: usize) -> Vec<u8> {
+        // non-zero, so it costs as much gas as random data
+        let word: Vec<u8> = (0..32).map(|_| fastrand::u8(1..)).collect();
+        word.iter().copied().cycle().take(size).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::ValueEnum;
+
+    #[test]
+    fn every_generator_fills_the_requested_size() {
+        for fill in DataFill::value_variants() {
+            let generator = fill.generator();
+            for size in [0, 1, 67, 68, 69, 4096] {
+                assert_eq!(
+                    generator.generate(size).len(),
+                    size,
+                    "{fill:?} at {size} bytes"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn erc20_calls_start_with_the_selector() {
+        let data = Erc20Transfers.generate(68 * 3);
+        for call in data.chunks(68) {
+            assert_eq!(call[..4], ERC20_TRANSFER_SELECTOR);
+        }
+    }
+}
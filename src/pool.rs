@@ -0,0 +1,145 @@
+use ethers::prelude::*;
+use eyre::Result;
+use futures::StreamExt;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::bundle_builder::{self, TxParams};
+use crate::rpc_batch::BatchSender;
+
+/// overwrite `path` with a pool of signed transactions: a JSON list of hex raw transactions
+pub fn write(path: &Path, transactions: &[Bytes]) -> Result<()> {
+    std::fs::write(path, serde_json::to_string_pretty(transactions)?)?;
+    Ok(())
+}
+
+pub fn read(path: &Path) -> Result<Vec<Bytes>> {
+    Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+}
+
+/// sign `count` mempool transactions of `chunk_size` calldata at nonces from `nonce` on,
+/// priced at `gas_price`, and write them to `path` for `blast`.  prints how long signing took.
+pub async fn prepare<M: Middleware>(
+    params: &TxParams,
+    client: &M,
+    count: usize,
+    nonce: U256,
+    chunk_size: usize,
+    gas_price: U256,
+    path: &Path,
+) -> Result<()>
+where
+    M::Error: 'static,
+{
+    bundle_builder::validate_chunk_size(chunk_size, true)?;
+    let calldata_bytes = bundle_builder::chunk_calldata_bytes(chunk_size);
+    let started = Instant::now();
+    let mut transactions = Vec::with_capacity(count);
+    for i in 0..count {
+        let tx = bundle_builder::get_signed_tx(
+            params,
+            calldata_bytes,
+            gas_price,
+            None,
+            client,
+            nonce + U256::from(i),
+        )
+        .await?;
+        transactions.push(tx);
+    }
+    let elapsed = started.elapsed().as_secs_f64();
+    write(path, &transactions)?;
+    println!(
+        "signed {count} transactions (nonces {nonce} to {}) at {gas_price} wei in {elapsed:.2}s, {:.1} tx/s; wrote {}",
+        nonce + U256::from(count.saturating_sub(1)),
+        count as f64 / elapsed.max(f64::EPSILON),
+        path.display()
+    );
+    Ok(())
+}
+
+/// submit every transaction in `transactions` as fast as the node takes them, `concurrency`
+/// requests at a time (each one a json-rpc batch of `batch_size` with a `batcher`), and print
+/// the submission throughput.  nothing is signed or waited on, so it's all send time.
+pub async fn blast(
+    provider: Arc<Provider<Http>>,
+    transactions: Vec<Bytes>,
+    concurrency: usize,
+    batcher: Option<(BatchSender, usize)>,
+) -> Result<()> {
+    if transactions.is_empty() {
+        eyre::bail!("the pool has no transactions to submit");
+    }
+    let bytes: usize = transactions.iter().map(|tx| tx.len()).sum();
+    let started = Instant::now();
+    let results: Vec<Result<TxHash, String>> = match &batcher {
+        Some((batcher, batch_size)) => {
+            let batches: Vec<_> = futures::stream::iter(transactions.chunks(*batch_size))
+                .map(|batch| async move {
+                    match batcher.send_raw_transactions(batch).await {
+                        Ok(results) => results,
+                        Err(err) => vec![Err(err.to_string()); batch.len()],
+                    }
+                })
+                .buffer_unordered(concurrency)
+                .collect()
+                .await;
+            batches.into_iter().flatten().collect()
+        }
+        None => {
+            futures::stream::iter(transactions.iter().cloned())
+                .map(|tx| {
+                    let provider = &provider;
+                    async move {
+                        provider
+                            .send_raw_transaction(tx)
+                            .await
+                            .map(|pending| *pending)
+                            .map_err(|err| err.to_string())
+                    }
+                })
+                .buffer_unordered(concurrency)
+                .collect()
+                .await
+        }
+    };
+    let elapsed = started.elapsed().as_secs_f64().max(f64::EPSILON);
+
+    let mut rejected = 0;
+    for result in &results {
+        if let Err(reason) = result {
+            rejected += 1;
+            tracing::debug!("node rejected a pool transaction: {reason}");
+        }
+    }
+    if let Some(Err(reason)) = results.iter().find(|result| result.is_err()) {
+        tracing::warn!("{rejected} transactions rejected, e.g. {reason}");
+    }
+    println!(
+        "submitted {} transactions ({bytes} bytes) in {elapsed:.2}s: {:.1} tx/s, {:.0} bytes/s; {} accepted, {rejected} rejected",
+        results.len(),
+        results.len() as f64 / elapsed,
+        bytes as f64 / elapsed,
+        results.len() - rejected
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pool_round_trips_as_hex() {
+        let path = std::env::temp_dir().join(format!("pool-{}.json", std::process::id()));
+        let transactions = vec![Bytes::from(vec![0x02, 0xf8]), Bytes::from(vec![0xab; 3])];
+        write(&path, &transactions).unwrap();
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        let hex: Vec<String> = serde_json::from_str(&text).unwrap();
+        assert_eq!(hex, vec!["0x02f8", "0xababab"]);
+        assert_eq!(read(&path).unwrap(), transactions);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,70 @@
+use clap::{parser::ValueSource, ArgMatches, ValueEnum};
+use serde::Serialize;
+
+use crate::{bundle_builder::KB, Opts};
+
+/// named scenarios that set a coherent group of sizing defaults
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Preset {
+    /// as much calldata as we've been able to land: 89% blocks of 128kb transactions
+    MaxCalldata,
+    /// half full blocks of mid-sized transactions
+    Realistic,
+    /// small blocks and a handful of transactions, for smoke testing a setup
+    Light,
+}
+
+/// the subset of `Opts` a preset fills in.  `None` leaves the flag's own default alone.
+#[derive(Debug, Default)]
+pub struct PresetOpts {
+    pub fill_pct: Option<f64>,
+    pub chunk_size: Option<usize>,
+    pub tip_wei: Option<u64>,
+    pub mempool_txs: Option<usize>,
+}
+
+pub fn preset_opts(preset: Preset) -> PresetOpts {
+    match preset {
+        Preset::MaxCalldata => PresetOpts {
+            fill_pct: Some(89.0),
+            chunk_size: Some(128 * KB),
+            tip_wei: Some(10_000_000_000),
+            mempool_txs: Some(128),
+        },
+        Preset::Realistic => PresetOpts {
+            fill_pct: Some(50.0),
+            chunk_size: Some(32 * KB),
+            tip_wei: Some(3_000_000_000),
+            mempool_txs: Some(64),
+        },
+        Preset::Light => PresetOpts {
+            fill_pct: Some(10.0),
+            chunk_size: Some(16 * KB),
+            tip_wei: Some(1_000_000_000),
+            mempool_txs: Some(8),
+        },
+    }
+}
+
+/// apply the preset to every field that wasn't explicitly passed on the command line
+pub fn apply(opts: &mut Opts, matches: &ArgMatches) {
+    let Some(preset) = opts.preset else {
+        return;
+    };
+    let preset = preset_opts(preset);
+    let explicit = |id: &str| matches.value_source(id) == Some(ValueSource::CommandLine);
+
+    if let (Some(fill_pct), false) = (preset.fill_pct, explicit("fill_pct")) {
+        opts.fill_pct = fill_pct;
+    }
+    if let (Some(chunk_size), false) = (preset.chunk_size, explicit("chunk_size")) {
+        opts.chunk_size = chunk_size;
+    }
+    if let (Some(tip_wei), false) = (preset.tip_wei, explicit("tip_wei")) {
+        opts.tip_wei = tip_wei;
+    }
+    if let (Some(mempool_txs), false) = (preset.mempool_txs, explicit("mempool_txs")) {
+        opts.mempool_txs = mempool_txs;
+    }
+}
@@ -0,0 +1,72 @@
+use std::time::Duration;
+
+/// the parts of a bundle iteration we time with `--profile`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// building and signing the bundle's transactions
+    Construct,
+    /// the `eth_sendBundle` round-trip, which includes the relay's own simulation
+    Submit,
+    /// waiting on the target block to find out whether the bundle landed
+    Inclusion,
+    /// idle until the block subscription hands us the next block
+    WaitForBlock,
+}
+
+const PHASES: [Phase; 4] = [
+    Phase::Construct,
+    Phase::Submit,
+    Phase::Inclusion,
+    Phase::WaitForBlock,
+];
+
+/// per-phase timings accumulated over a run
+#[derive(Debug, Default)]
+pub struct Profile {
+    samples: Vec<(Phase, Duration)>,
+}
+
+impl Profile {
+    pub fn record(&mut self, phase: Phase, elapsed: Duration) {
+        self.samples.push((phase, elapsed));
+    }
+
+    /// total/mean/max per phase, plus each phase's share of the total time
+    pub fn report(&self) -> String {
+        let overall: Duration = self.samples.iter().map(|(_, elapsed)| *elapsed).sum();
+        let mut report = format!(
+            "{:<16}{:>8}{:>12}{:>12}{:>12}{:>8}\n",
+            "phase", "count", "total_ms", "mean_ms", "max_ms", "share"
+        );
+        for phase in PHASES {
+            let timings: Vec<Duration> = self
+                .samples
+                .iter()
+                .filter(|(p, _)| *p == phase)
+                .map(|(_, elapsed)| *elapsed)
+                .collect();
+            let total: Duration = timings.iter().sum();
+            let max = timings.iter().max().copied().unwrap_or_default();
+            let mean = if timings.is_empty() {
+                Duration::ZERO
+            } else {
+                total / timings.len() as u32
+            };
+            let share = if overall.is_zero() {
+                0.0
+            } else {
+                total.as_secs_f64() * 100.0 / overall.as_secs_f64()
+            };
+            report.push_str(&format!(
+                "{:<16}{:>8}{:>12}{:>12}{:>12}{:>7.1}%\n",
+                format!("{phase:?}"),
+                timings.len(),
+                total.as_millis(),
+                mean.as_millis(),
+                max.as_millis(),
+                share
+            ));
+        }
+        report
+    }
+}
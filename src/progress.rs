@@ -0,0 +1,56 @@
+use indicatif::{ProgressBar, ProgressStyle};
+use std::io::IsTerminal;
+use std::time::Duration;
+
+/// in-place status line for long bundle runs, shown instead of the scrolling logs
+pub struct Progress {
+    bar: Option<ProgressBar>,
+}
+
+impl Progress {
+    /// only draws when asked for and stderr is a terminal; otherwise every call is a no-op
+    /// and the normal tracing output is left alone.
+    pub fn new(enabled: bool) -> Self {
+        if !enabled || !is_tty() {
+            return Self { bar: None };
+        }
+
+        let bar = ProgressBar::new_spinner();
+        bar.set_style(
+            ProgressStyle::with_template("{spinner} [{elapsed_precise}] {msg}")
+                .unwrap_or_else(|_| ProgressStyle::default_spinner()),
+        );
+        bar.enable_steady_tick(Duration::from_millis(250));
+        bar.set_message("waiting for next block");
+        Self { bar: Some(bar) }
+    }
+
+    pub fn update(&self, block: u64, landed: usize, attempted: usize, tip_wei: u64, nonce: u64) {
+        if let Some(bar) = &self.bar {
+            let success_rate = success_rate(landed, attempted);
+            bar.set_message(format!(
+                "block {block} | landed {landed}/{attempted} ({success_rate:.1}%) | tip {tip_wei} wei | nonce {nonce}"
+            ));
+        }
+    }
+
+    pub fn finish(&self) {
+        if let Some(bar) = &self.bar {
+            bar.finish();
+        }
+    }
+}
+
+/// percentage of attempted bundles that landed
+pub fn success_rate(landed: usize, attempted: usize) -> f64 {
+    if attempted == 0 {
+        0.0
+    } else {
+        landed as f64 * 100.0 / attempted as f64
+    }
+}
+
+/// the progress line is drawn on stderr, so that's the stream that has to be a terminal
+pub fn is_tty() -> bool {
+    std::io::stderr().is_terminal()
+}
@@ -0,0 +1,43 @@
+use ethers::prelude::*;
+use eyre::{Result, WrapErr};
+use serde::Deserialize;
+use std::path::Path;
+use url::Url;
+
+/// one relay to submit bundles to, and the key whose reputation we sign its requests with
+#[derive(Debug, Clone)]
+pub struct RelayEntry {
+    pub url: Url,
+    pub signer: LocalWallet,
+}
+
+#[derive(Deserialize)]
+struct RawEntry {
+    url: String,
+    signer: String,
+}
+
+/// read a `--relay-list-file`: a json array of `{"url": ..., "signer": ...}` objects, where
+/// `signer` is the private key to sign requests to that relay with.  every key is parsed here,
+/// so a typo fails the run before anything is submitted.
+pub fn load(path: &Path) -> Result<Vec<RelayEntry>> {
+    let raw: Vec<RawEntry> = serde_json::from_str(&std::fs::read_to_string(path)?)
+        .wrap_err_with(|| format!("reading relay list {}", path.display()))?;
+    if raw.is_empty() {
+        eyre::bail!("relay list {} has no relays", path.display());
+    }
+    raw.into_iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let url = Url::parse(&entry.url)
+                .wrap_err_with(|| format!("relay {i} has an invalid url {}", entry.url))?;
+            let signer = entry
+                .signer
+                .strip_prefix("0x")
+                .unwrap_or(&entry.signer)
+                .parse::<LocalWallet>()
+                .wrap_err_with(|| format!("relay {i} ({url}) has an invalid signer key"))?;
+            Ok(RelayEntry { url, signer })
+        })
+        .collect()
+}
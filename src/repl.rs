@@ -0,0 +1,170 @@
+use ethers::prelude::*;
+use ethers_flashbots::FlashbotsMiddleware;
+use eyre::Result;
+use std::io::Write;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use url::Url;
+
+use crate::bundle_builder::{self, TxParams};
+use crate::BundleClient;
+
+const HELP: &str = "\
+set fill <pct>     percentage of the block to fill, e.g. `set fill 80`
+set tip <amount>   tip per gas, in wei or with a unit: `set tip 10gwei`
+set chunk <size>   calldata per transaction: `set chunk 128kb`
+submit             build a bundle for the next block, send it and wait for it to land
+stats              current settings and bundles landed so far
+help               this list
+quit               exit";
+
+/// what `submit` builds with, changed by `set`
+struct Settings {
+    fill_pct: f64,
+    chunk_size: usize,
+    tip_wei: u64,
+}
+
+/// read `set`/`submit`/`stats` commands from stdin, keeping one relay connection alive for all
+/// of them.  a failed command prints its error and waits for the next.
+pub async fn run(
+    provider: Arc<Provider<Http>>,
+    signer: LocalWallet,
+    bundle_signer: LocalWallet,
+    relay_url: Url,
+    params: TxParams,
+    opts: &crate::Opts,
+) -> Result<()> {
+    let relay = FlashbotsMiddleware::new(provider.clone(), relay_url.clone(), bundle_signer);
+    let client: BundleClient = SignerMiddleware::new_with_provider_chain(relay, signer).await?;
+    let mut settings = Settings {
+        fill_pct: opts.fill_pct,
+        chunk_size: opts.chunk_size,
+        tip_wei: opts.tip_wei,
+    };
+    let (mut attempted, mut landed) = (0, 0);
+
+    println!("connected to {relay_url}, `help` lists the commands");
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    loop {
+        print!("> ");
+        std::io::stdout().flush()?;
+        let Some(line) = lines.next_line().await? else {
+            break;
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let result = match words.as_slice() {
+            [] => Ok(()),
+            ["help"] => {
+                println!("{HELP}");
+                Ok(())
+            }
+            ["quit" | "exit"] => break,
+            ["stats"] => {
+                println!(
+                    "fill {}%, chunk {} bytes, tip {} wei; landed {landed}/{attempted} ({:.1}%)",
+                    settings.fill_pct,
+                    settings.chunk_size,
+                    settings.tip_wei,
+                    crate::progress::success_rate(landed, attempted)
+                );
+                Ok(())
+            }
+            ["set", name, value] => set(&mut settings, name, value),
+            ["submit"] => {
+                attempted += 1;
+                let result = submit(&client, &params, &settings).await;
+                if let Ok(true) = result {
+                    landed += 1;
+                }
+                result.map(|_| ())
+            }
+            _ => Err(eyre::eyre!("unknown command `{line}`, try `help`")),
+        };
+        if let Err(err) = result {
+            println!("error: {err}");
+        }
+    }
+    Ok(())
+}
+
+fn set(settings: &mut Settings, name: &str, value: &str) -> Result<()> {
+    match name {
+        "fill" => settings.fill_pct = crate::fill_percent(value).map_err(eyre::Report::msg)?,
+        "chunk" => settings.chunk_size = crate::data_size(value).map_err(eyre::Report::msg)?,
+        "tip" => settings.tip_wei = parse_tip(value)?,
+        _ => eyre::bail!("can only set fill, tip or chunk, not `{name}`"),
+    }
+    Ok(())
+}
+
+/// wei, or a number with a `wei`/`gwei`/`ether` unit
+fn parse_tip(value: &str) -> Result<u64> {
+    let value = value.trim().to_ascii_lowercase();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(split);
+    let unit = match unit {
+        "" => "wei",
+        "eth" => "ether",
+        unit => unit,
+    };
+    let wei: U256 = ethers::utils::parse_units(amount, unit)?;
+    if wei > U256::from(u64::MAX) {
+        eyre::bail!("tip of {wei} wei is too large");
+    }
+    Ok(wei.as_u64())
+}
+
+/// sign a bundle at the current nonce, send it for the next block and wait for it.  true if it
+/// landed.
+async fn submit(client: &BundleClient, params: &TxParams, settings: &Settings) -> Result<bool> {
+    let head = client.get_block_number().await?;
+    let block = client
+        .get_block(head)
+        .await?
+        .ok_or_else(|| eyre::eyre!("no block {head}"))?;
+    let nonce = client
+        .get_transaction_count(params.address, Some(BlockNumber::Pending.into()))
+        .await?;
+    let normal_nonce = match &params.normal_tx_signer {
+        Some(signer) => Some(
+            client
+                .get_transaction_count(signer.address(), Some(BlockNumber::Pending.into()))
+                .await?,
+        ),
+        None => None,
+    };
+    let bundle = bundle_builder::construct_bundle(
+        params,
+        client,
+        block.gas_limit,
+        settings.fill_pct,
+        nonce,
+        normal_nonce,
+        settings.chunk_size,
+        settings.tip_wei,
+    )
+    .await?
+    .set_block(head + 1)
+    .set_simulation_block(head)
+    .set_simulation_timestamp(0);
+    println!(
+        "sending {} transactions ({} bytes) for block {}",
+        bundle.transactions().len(),
+        bundle_builder::bundle_size_bytes(&bundle),
+        head + 1
+    );
+    let pending = client.inner().send_bundle(&bundle).await?;
+    match pending.await {
+        Ok(hash) => {
+            println!("landed in block {}, bundle {hash:?}", head + 1);
+            Ok(true)
+        }
+        Err(err) => {
+            println!("did not land: {err}");
+            Ok(false)
+        }
+    }
+}
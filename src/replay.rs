@@ -0,0 +1,117 @@
+use ethers::prelude::*;
+use ethers_flashbots::{BundleRequest, FlashbotsMiddleware};
+use eyre::Result;
+
+use crate::error::StressError;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+use url::Url;
+
+/// a bundle as written by `--dump-bundle`: its signed transactions and the block it targeted
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DumpedBundle {
+    pub block: Option<U64>,
+    pub transactions: Vec<Bytes>,
+}
+
+impl DumpedBundle {
+    pub fn from_bundle(bundle: &BundleRequest) -> Self {
+        Self {
+            block: bundle.block(),
+            transactions: crate::bundle_builder::raw_transactions(bundle),
+        }
+    }
+
+    /// overwrite `path` with this bundle
+    pub fn write(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// hashes of the signed transactions, in bundle order
+    pub fn tx_hashes(&self) -> Vec<TxHash> {
+        self.transactions
+            .iter()
+            .map(|raw| H256::from(ethers::utils::keccak256(raw)))
+            .collect()
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+    }
+}
+
+/// check every sender's transactions still start at its current nonce and have no gaps,
+/// otherwise the relay would just drop the bundle
+async fn check_nonces(
+    provider: &Provider<Http>,
+    transactions: &[Transaction],
+) -> crate::error::Result<()> {
+    let mut by_sender: BTreeMap<Address, Vec<U256>> = BTreeMap::new();
+    for tx in transactions {
+        by_sender.entry(tx.from).or_default().push(tx.nonce);
+    }
+    for (sender, nonces) in by_sender {
+        let current = provider
+            .get_transaction_count(sender, None)
+            .await
+            .map_err(StressError::rpc)?;
+        for (i, nonce) in nonces.iter().enumerate() {
+            if *nonce != current + U256::from(i) {
+                return Err(StressError::NonceGap {
+                    sender,
+                    current,
+                    nonce: *nonce,
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// resubmit the bundle dumped at `path`, as-is, for the block after the current head
+pub async fn run(
+    provider: Arc<Provider<Http>>,
+    bundle_signer: LocalWallet,
+    relay_url: Url,
+    path: &Path,
+) -> Result<()> {
+    let dumped = DumpedBundle::read(path)?;
+    let mut transactions = Vec::new();
+    for raw in &dumped.transactions {
+        let mut tx = crate::bundle_builder::decode_raw(raw)?;
+        if tx.from.is_zero() {
+            tx.from = tx.recover_from()?;
+        }
+        transactions.push(tx);
+    }
+    check_nonces(&provider, &transactions).await?;
+
+    let head = provider.get_block_number().await?;
+    let mut bundle = BundleRequest::new();
+    for raw in dumped.transactions {
+        bundle = bundle.push_transaction(raw);
+    }
+    let bundle = bundle
+        .set_block(head + 1)
+        .set_simulation_block(head)
+        .set_simulation_timestamp(0);
+    tracing::info!(
+        "replaying {} transactions from {} (originally for block {:?}) at block {}",
+        transactions.len(),
+        path.display(),
+        dumped.block,
+        head + 1
+    );
+
+    let relay = FlashbotsMiddleware::new(provider, relay_url, bundle_signer);
+    match relay.send_bundle(&bundle).await?.await {
+        Ok(bundle_hash) => {
+            tracing::info!("replay landed in block {}! hash: {bundle_hash:?}", head + 1)
+        }
+        Err(err) => tracing::warn!("replay did not land: {err}"),
+    }
+    Ok(())
+}
@@ -0,0 +1,118 @@
+use ethers::prelude::*;
+use reqwest::header::{HeaderMap, CONTENT_TYPE};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+/// sends raw transactions in JSON-RPC batches, one http request for many
+/// `eth_sendRawTransaction` calls
+pub struct BatchSender {
+    client: reqwest::Client,
+    url: Url,
+}
+
+#[derive(Serialize)]
+struct Call<'a> {
+    jsonrpc: &'static str,
+    id: usize,
+    method: &'static str,
+    params: [&'a Bytes; 1],
+}
+
+#[derive(Deserialize)]
+struct Response {
+    id: usize,
+    result: Option<TxHash>,
+    error: Option<RpcError>,
+}
+
+#[derive(Deserialize)]
+struct RpcError {
+    message: String,
+}
+
+/// the node answered something other than a batch response, so it doesn't take batches
+#[derive(Debug, thiserror::Error)]
+#[error("node doesn't support json-rpc batches: {0}")]
+pub struct Unsupported(String);
+
+impl BatchSender {
+    /// a sender for the node at `url`, sending `headers` (`--rpc-header`) with every batch
+    pub fn new(url: Url, headers: HeaderMap) -> eyre::Result<Self> {
+        let client = reqwest::Client::builder()
+            .default_headers(headers)
+            .build()?;
+        Ok(Self { client, url })
+    }
+
+    /// send `txs` as one batch.  each transaction's hash, or the node's reason for rejecting
+    /// it, in the order of `txs`.
+    pub async fn send_raw_transactions(
+        &self,
+        txs: &[Bytes],
+    ) -> Result<Vec<Result<TxHash, String>>, Unsupported> {
+        let calls: Vec<_> = txs
+            .iter()
+            .enumerate()
+            .map(|(id, tx)| Call {
+                jsonrpc: "2.0",
+                id,
+                method: "eth_sendRawTransaction",
+                params: [tx],
+            })
+            .collect();
+        let body = serde_json::to_vec(&calls).map_err(|err| Unsupported(err.to_string()))?;
+        let response = self
+            .client
+            .post(self.url.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| Unsupported(err.to_string()))?;
+        let body = response
+            .bytes()
+            .await
+            .map_err(|err| Unsupported(err.to_string()))?;
+        parse_responses(&body, txs.len())
+    }
+}
+
+/// match the batch's responses, which can come back in any order, to the calls by id
+fn parse_responses(body: &[u8], count: usize) -> Result<Vec<Result<TxHash, String>>, Unsupported> {
+    let responses: Vec<Response> = serde_json::from_slice(body)
+        .map_err(|_| Unsupported(String::from_utf8_lossy(body).into_owned()))?;
+    let mut results = vec![Err("no response".to_string()); count];
+    for response in responses {
+        if let Some(slot) = results.get_mut(response.id) {
+            *slot = match (response.result, response.error) {
+                (Some(hash), _) => Ok(hash),
+                (None, Some(error)) => Err(error.message),
+                (None, None) => Err("empty response".to_string()),
+            };
+        }
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn responses_are_matched_by_id() {
+        let hash = TxHash::repeat_byte(0x11);
+        let body = format!(
+            r#"[{{"jsonrpc":"2.0","id":1,"error":{{"code":-32000,"message":"nonce too low"}}}},
+               {{"jsonrpc":"2.0","id":0,"result":"{hash:?}"}}]"#
+        );
+        let results = parse_responses(body.as_bytes(), 3).unwrap();
+        assert_eq!(results[0], Ok(hash));
+        assert_eq!(results[1], Err("nonce too low".to_string()));
+        assert!(results[2].is_err());
+
+        let single =
+            r#"{"jsonrpc":"2.0","id":null,"error":{"code":-32600,"message":"batch not allowed"}}"#;
+        assert!(parse_responses(single.as_bytes(), 1).is_err());
+    }
+}
@@ -0,0 +1,101 @@
+use serde::Serialize;
+
+/// how a run's mempool transactions are split between the signers
+#[derive(Clone, Copy, Debug, PartialEq, Eq, clap::ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NonceDistribution {
+    /// each signer sends one consecutive run of the transactions
+    Contiguous,
+    /// the signers take turns, one transaction each
+    Interleaved,
+}
+
+/// one scheduled transaction: the index of the signer sending it, and its nonce relative to
+/// that signer's starting nonce
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Slot {
+    pub signer: usize,
+    pub nonce_offset: u64,
+}
+
+/// assign `txs` transactions, in submission order, to `signers` signers.  contiguous slices are
+/// as even as possible, with the first signers taking one extra when it doesn't divide.
+pub fn schedule(signers: usize, txs: usize, distribution: NonceDistribution) -> Vec<Slot> {
+    assert!(signers > 0, "need at least one signer");
+    match distribution {
+        NonceDistribution::Contiguous => {
+            let (per_signer, extra) = (txs / signers, txs % signers);
+            (0..signers)
+                .flat_map(|signer| {
+                    let count = per_signer + usize::from(signer < extra);
+                    (0..count as u64).map(move |nonce_offset| Slot {
+                        signer,
+                        nonce_offset,
+                    })
+                })
+                .collect()
+        }
+        NonceDistribution::Interleaved => (0..txs)
+            .map(|i| Slot {
+                signer: i % signers,
+                nonce_offset: (i / signers) as u64,
+            })
+            .collect(),
+    }
+}
+
+/// how many of the scheduled transactions each signer sends
+pub fn per_signer_counts(slots: &[Slot], signers: usize) -> Vec<usize> {
+    let mut counts = vec![0; signers];
+    for slot in slots {
+        counts[slot.signer] += 1;
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pairs(slots: &[Slot]) -> Vec<(usize, u64)> {
+        slots.iter().map(|s| (s.signer, s.nonce_offset)).collect()
+    }
+
+    #[test]
+    fn contiguous_uneven_split() {
+        let slots = schedule(3, 7, NonceDistribution::Contiguous);
+        assert_eq!(
+            pairs(&slots),
+            vec![(0, 0), (0, 1), (0, 2), (1, 0), (1, 1), (2, 0), (2, 1)]
+        );
+        assert_eq!(per_signer_counts(&slots, 3), vec![3, 2, 2]);
+    }
+
+    #[test]
+    fn interleaved_takes_turns() {
+        let slots = schedule(3, 7, NonceDistribution::Interleaved);
+        assert_eq!(
+            pairs(&slots),
+            vec![(0, 0), (1, 0), (2, 0), (0, 1), (1, 1), (2, 1), (0, 2)]
+        );
+        assert_eq!(per_signer_counts(&slots, 3), vec![3, 2, 2]);
+    }
+
+    #[test]
+    fn single_signer_gets_consecutive_nonces() {
+        for distribution in [
+            NonceDistribution::Contiguous,
+            NonceDistribution::Interleaved,
+        ] {
+            let slots = schedule(1, 4, distribution);
+            assert_eq!(pairs(&slots), vec![(0, 0), (0, 1), (0, 2), (0, 3)]);
+        }
+    }
+
+    #[test]
+    fn more_signers_than_transactions() {
+        let slots = schedule(4, 2, NonceDistribution::Contiguous);
+        assert_eq!(per_signer_counts(&slots, 4), vec![1, 1, 0, 0]);
+        assert_eq!(schedule(4, 0, NonceDistribution::Interleaved), vec![]);
+    }
+}
@@ -0,0 +1,113 @@
+use ethers::prelude::*;
+use ethers::types::transaction::eip2718::TypedTransaction;
+use ethers_flashbots::{BundleRequest, FlashbotsMiddleware};
+use eyre::Result;
+use std::sync::Arc;
+use url::Url;
+
+use crate::error::StressError;
+
+/// check the rpc endpoint, the tx signer's balance and the relay without submitting anything,
+/// printing OK/FAIL per check.  errors if any check failed.
+pub async fn run(
+    provider: Arc<Provider<Http>>,
+    signer: LocalWallet,
+    bundle_signer: LocalWallet,
+    relay_url: Url,
+) -> Result<()> {
+    let mut failed = 0;
+    let mut report = |check: &str, result: Result<String>| match result {
+        Ok(detail) => println!("OK   {check}: {detail}"),
+        Err(err) => {
+            failed += 1;
+            println!("FAIL {check}: {err}");
+        }
+    };
+
+    let head = async {
+        let chain_id = provider.get_chainid().await?;
+        let head = provider.get_block_number().await?;
+        Ok::<_, eyre::Report>((chain_id, head))
+    }
+    .await;
+    let head = match head {
+        Ok((chain_id, head)) => {
+            report("rpc", Ok(format!("chain id {chain_id}, head block {head}")));
+            Some(head)
+        }
+        Err(err) => {
+            report("rpc", Err(err));
+            None
+        }
+    };
+
+    let balance = async {
+        let balance = provider.get_balance(signer.address(), None).await?;
+        let eth = ethers::core::utils::format_units(balance, "eth")?;
+        if balance.is_zero() {
+            return Err(StressError::InsufficientBalance {
+                address: signer.address(),
+                balance,
+            }
+            .into());
+        }
+        Ok(format!("{:?} holds {eth} ETH", signer.address()))
+    }
+    .await;
+    report("balance", balance);
+
+    let relay = match head {
+        Some(head) => {
+            simulate_noop(provider.clone(), &signer, bundle_signer, relay_url, head).await
+        }
+        None => Err(eyre::eyre!("skipped, no head block from the rpc")),
+    };
+    report("relay", relay);
+
+    if failed > 0 {
+        eyre::bail!("{failed} self-test check(s) failed");
+    }
+    Ok(())
+}
+
+/// simulate (never send) a bundle of one zero-value self transfer on top of `head`
+async fn simulate_noop(
+    provider: Arc<Provider<Http>>,
+    signer: &LocalWallet,
+    bundle_signer: LocalWallet,
+    relay_url: Url,
+    head: U64,
+) -> Result<String> {
+    let address = signer.address();
+    let nonce = provider
+        .get_transaction_count(address, Some(BlockNumber::Pending.into()))
+        .await?;
+    let chain_id = provider.get_chainid().await?.as_u64();
+    let gas_price = provider.get_gas_price().await?;
+    let tx: TypedTransaction = TransactionRequest::new()
+        .chain_id(chain_id)
+        .from(address)
+        .to(address)
+        .value(0)
+        .gas(21_000)
+        .gas_price(gas_price)
+        .nonce(nonce)
+        .into();
+    let signature = signer
+        .clone()
+        .with_chain_id(chain_id)
+        .sign_transaction(&tx)
+        .await?;
+
+    let bundle = BundleRequest::new()
+        .push_transaction(tx.rlp_signed(&signature))
+        .set_block(head + 1)
+        .set_simulation_block(head)
+        .set_simulation_timestamp(0);
+    let relay = FlashbotsMiddleware::new(provider, relay_url.clone(), bundle_signer);
+    let simulated = relay.simulate_bundle(&bundle).await?;
+    Ok(format!(
+        "{relay_url} simulated a no-op bundle on block {} ({} gas)",
+        simulated.simulation_block, simulated.gas_used
+    ))
+}
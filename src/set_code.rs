@@ -0,0 +1,233 @@
+use ethers::prelude::*;
+use ethers::utils::keccak256;
+use ethers::utils::rlp::{DecoderError, Rlp, RlpStream};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::error::{Result, StressError};
+
+/// the EIP-7702 set-code transaction type
+pub const TX_TYPE: u8 = 0x04;
+
+/// gas each authorization adds to a transaction (`PER_EMPTY_ACCOUNT_COST`), which
+/// `estimate_gas` on a plain transaction can't see
+pub const PER_AUTHORIZATION_GAS: u64 = 25_000;
+
+/// one signed authorization, in the JSON-RPC format
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Authorization {
+    pub chain_id: U256,
+    pub address: Address,
+    pub nonce: U64,
+    pub y_parity: U64,
+    pub r: U256,
+    pub s: U256,
+}
+
+impl Authorization {
+    fn rlp_append(&self, stream: &mut RlpStream) {
+        stream.begin_list(6);
+        stream.append(&self.chain_id);
+        stream.append(&self.address);
+        stream.append(&self.nonce);
+        stream.append(&self.y_parity);
+        stream.append(&self.r);
+        stream.append(&self.s);
+    }
+
+    fn decode(rlp: &Rlp) -> std::result::Result<Self, DecoderError> {
+        Ok(Self {
+            chain_id: rlp.val_at(0)?,
+            address: rlp.val_at(1)?,
+            nonce: rlp.val_at(2)?,
+            y_parity: rlp.val_at(3)?,
+            r: rlp.val_at(4)?,
+            s: rlp.val_at(5)?,
+        })
+    }
+}
+
+/// `--authorization-list`: a JSON list of signed authorizations every set-code transaction
+/// carries
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthorizationList {
+    path: String,
+    pub authorizations: Vec<Authorization>,
+}
+
+impl AuthorizationList {
+    pub fn load(path: &str) -> std::result::Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+        let authorizations: Vec<Authorization> =
+            serde_json::from_str(&text).map_err(|e| format!("{path}: {e}"))?;
+        if authorizations.is_empty() {
+            return Err(format!(
+                "{path}: a set-code transaction needs at least one authorization"
+            ));
+        }
+        Ok(Self {
+            path: path.to_string(),
+            authorizations,
+        })
+    }
+}
+
+impl Serialize for AuthorizationList {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.path)
+    }
+}
+
+/// ethers 1.0 has no type-4 transaction, so this is its envelope, encoded by hand
+#[derive(Debug, Clone, PartialEq)]
+pub struct SetCodeTx {
+    pub chain_id: u64,
+    pub nonce: U256,
+    pub max_priority_fee_per_gas: U256,
+    pub max_fee_per_gas: U256,
+    pub gas: U256,
+    pub to: Address,
+    pub value: U256,
+    pub data: Bytes,
+    pub authorizations: Vec<Authorization>,
+}
+
+impl SetCodeTx {
+    /// everything but the signature, in envelope order
+    fn rlp_append_fields(&self, stream: &mut RlpStream) {
+        stream.append(&self.chain_id);
+        stream.append(&self.nonce);
+        stream.append(&self.max_priority_fee_per_gas);
+        stream.append(&self.max_fee_per_gas);
+        stream.append(&self.gas);
+        stream.append(&self.to);
+        stream.append(&self.value);
+        stream.append(&self.data.to_vec());
+        // no access list
+        stream.begin_list(0);
+        stream.begin_list(self.authorizations.len());
+        for authorization in &self.authorizations {
+            authorization.rlp_append(stream);
+        }
+    }
+
+    /// what the sender signs: keccak256(0x04 ‖ rlp(fields))
+    pub fn sighash(&self) -> H256 {
+        let mut stream = RlpStream::new_list(10);
+        self.rlp_append_fields(&mut stream);
+        H256::from(keccak256([&[TX_TYPE], stream.as_raw()].concat()))
+    }
+
+    pub fn sign(&self, wallet: &LocalWallet) -> Bytes {
+        let signature = wallet.sign_hash(self.sighash());
+        let mut stream = RlpStream::new_list(13);
+        self.rlp_append_fields(&mut stream);
+        // `sign_hash` leaves v at 27 or 28
+        stream.append(&(signature.v - 27));
+        stream.append(&signature.r);
+        stream.append(&signature.s);
+        [&[TX_TYPE], stream.as_raw()].concat().into()
+    }
+}
+
+/// decode a signed set-code transaction into the fields of an ethers `Transaction` that the
+/// rest of the tool reads, with the sender recovered into `from`
+pub fn decode(raw: &[u8]) -> Result<Transaction> {
+    let body = match raw.split_first() {
+        Some((&TX_TYPE, body)) => body,
+        _ => return Err(DecoderError::Custom("not a set-code transaction").into()),
+    };
+    let rlp = Rlp::new(body);
+    let authorizations = rlp
+        .at(9)?
+        .iter()
+        .map(|authorization| Authorization::decode(&authorization))
+        .collect::<std::result::Result<Vec<_>, _>>()?;
+    let tx = SetCodeTx {
+        chain_id: rlp.val_at(0)?,
+        nonce: rlp.val_at(1)?,
+        max_priority_fee_per_gas: rlp.val_at(2)?,
+        max_fee_per_gas: rlp.val_at(3)?,
+        gas: rlp.val_at(4)?,
+        to: rlp.val_at(5)?,
+        value: rlp.val_at(6)?,
+        data: rlp.val_at::<Vec<u8>>(7)?.into(),
+        authorizations,
+    };
+    let y_parity: u64 = rlp.val_at(10)?;
+    let signature = Signature {
+        r: rlp.val_at(11)?,
+        s: rlp.val_at(12)?,
+        v: y_parity + 27,
+    };
+    let from = signature
+        .recover(tx.sighash())
+        .map_err(|err| StressError::InvalidConfig(err.to_string()))?;
+    Ok(Transaction {
+        hash: H256::from(keccak256(raw)),
+        nonce: tx.nonce,
+        from,
+        to: Some(tx.to),
+        value: tx.value,
+        gas: tx.gas,
+        input: tx.data,
+        v: y_parity.into(),
+        r: signature.r,
+        s: signature.s,
+        transaction_type: Some(U64::from(TX_TYPE)),
+        max_priority_fee_per_gas: Some(tx.max_priority_fee_per_gas),
+        max_fee_per_gas: Some(tx.max_fee_per_gas),
+        chain_id: Some(U256::from(tx.chain_id)),
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn set_code_tx_round_trips_through_rlp() {
+        let wallet: LocalWallet =
+            "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318"
+                .parse()
+                .unwrap();
+        let authorizations: Vec<Authorization> = serde_json::from_str(
+            r#"[{"chainId":"0x1","address":"0x00000000000000000000000000000000000000aa",
+                 "nonce":"0x7","yParity":"0x1","r":"0x2a","s":"0x2b"}]"#,
+        )
+        .unwrap();
+        let tx = SetCodeTx {
+            chain_id: 1,
+            nonce: U256::from(42),
+            max_priority_fee_per_gas: U256::from(2),
+            max_fee_per_gas: U256::from(7),
+            gas: U256::from(100_000),
+            to: Address::repeat_byte(0x11),
+            value: U256::zero(),
+            data: vec![0xab; 1000].into(),
+            authorizations: authorizations.clone(),
+        };
+        let raw = tx.sign(&wallet);
+        assert_eq!(raw[0], TX_TYPE);
+
+        let decoded = decode(&raw).unwrap();
+        assert_eq!(decoded.from, wallet.address());
+        assert_eq!(decoded.transaction_type, Some(U64::from(4)));
+        assert_eq!(decoded.nonce, U256::from(42));
+        assert_eq!(decoded.to, Some(tx.to));
+        assert_eq!(decoded.input.len(), 1000);
+        assert_eq!(decoded.max_fee_per_gas, Some(U256::from(7)));
+        assert_eq!(decoded.max_priority_fee_per_gas, Some(U256::from(2)));
+        assert_eq!(decoded.hash, H256::from(keccak256(&raw)));
+
+        // the authorization list survives
+        let body = Rlp::new(&raw[1..]);
+        let list = body.at(9).unwrap();
+        assert_eq!(
+            Authorization::decode(&list.at(0).unwrap()).unwrap(),
+            authorizations[0]
+        );
+        assert!(decode(&raw[1..]).is_err());
+    }
+}
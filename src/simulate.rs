@@ -0,0 +1,95 @@
+use ethers::prelude::*;
+use ethers_flashbots::{
+    BundleRequest, FlashbotsMiddleware, FlashbotsMiddlewareError, SimulatedBundle,
+};
+use eyre::Result;
+use serde::Serialize;
+use std::sync::Arc;
+use url::Url;
+
+/// a bundle with the `gasLimit` of the simulated block set, which `BundleRequest` has no
+/// setter for
+#[derive(Serialize)]
+struct GasLimitedBundle<'a> {
+    #[serde(flatten)]
+    bundle: &'a BundleRequest,
+    #[serde(rename = "gasLimit")]
+    gas_limit: U256,
+}
+
+/// `simulate_bundle`, in a block with `gas_limit` rather than whatever default the relay
+/// picks, which can be too low for a full bundle
+pub async fn call_bundle(
+    relay: &crate::Relay,
+    bundle: &BundleRequest,
+    gas_limit: U256,
+) -> Result<SimulatedBundle, FlashbotsMiddlewareError<Arc<Provider<Http>>, LocalWallet>> {
+    bundle
+        .block()
+        .and(bundle.simulation_block())
+        .and(bundle.simulation_timestamp())
+        .ok_or(FlashbotsMiddlewareError::MissingParameters)?;
+    relay
+        .relay()
+        .request("eth_callBundle", [GasLimitedBundle { bundle, gas_limit }])
+        .await
+        .map_err(FlashbotsMiddlewareError::RelayError)
+}
+
+/// have the relay simulate `bundle` for block `target` with a `gas_limit` on top of the state
+/// at block `state`, and print its total gas, what it pays the coinbase and how each
+/// transaction fared.
+/// nothing is submitted for inclusion, so the bundle signer needs no reputation.  errors if
+/// any transaction failed or reverted.
+pub async fn run(
+    provider: Arc<Provider<Http>>,
+    bundle: BundleRequest,
+    bundle_signer: LocalWallet,
+    relay_url: Url,
+    target: U64,
+    state: U64,
+    gas_limit: U256,
+) -> Result<()> {
+    let bundle = bundle
+        .set_block(target)
+        .set_simulation_block(state)
+        .set_simulation_timestamp(0);
+    let relay = FlashbotsMiddleware::new(provider, relay_url.clone(), bundle_signer);
+    let simulated = call_bundle(&relay, &bundle, gas_limit).await?;
+
+    println!(
+        "{relay_url} simulated {} transactions on block {}",
+        simulated.transactions.len(),
+        simulated.simulation_block
+    );
+    println!("total gas used:   {}", simulated.gas_used);
+    println!(
+        "coinbase payment: {} ETH ({} ETH of it sent directly)",
+        ethers::utils::format_units(simulated.coinbase_diff, "ether")?,
+        ethers::utils::format_units(simulated.coinbase_tip, "ether")?
+    );
+    println!("bundle gas price: {} wei", simulated.gas_price);
+
+    let mut failed = 0;
+    for (i, tx) in simulated.transactions.iter().enumerate() {
+        match (&tx.error, &tx.revert) {
+            (None, None) => println!("OK     tx {i} {:?}: {} gas", tx.hash, tx.gas_used),
+            (error, revert) => {
+                failed += 1;
+                let reason = revert
+                    .as_ref()
+                    .or(error.as_ref())
+                    .cloned()
+                    .unwrap_or_default();
+                println!("REVERT tx {i} {:?}: {} gas, {reason}", tx.hash, tx.gas_used);
+            }
+        }
+    }
+    if failed > 0 {
+        eyre::bail!(
+            "{failed}/{} transactions in the simulated bundle failed",
+            simulated.transactions.len()
+        );
+    }
+    Ok(())
+}
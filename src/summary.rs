@@ -0,0 +1,209 @@
+use clap::ValueEnum;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// how the end-of-run summary gets reported
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputFormat {
+    /// pretty-printed to stderr
+    Table,
+    /// a single json object on stdout
+    Json,
+    /// one row appended to `--summary-csv`
+    Csv,
+}
+
+/// the run so far and the last block it got to, kept up to date so a panic can still report it
+static PARTIAL: Mutex<Option<(Summary, u64)>> = Mutex::new(None);
+
+/// remember `summary`, as of `block`, as what to report if the run panics from here on
+pub fn record_partial(summary: &Summary, block: u64) {
+    let mut partial = PARTIAL
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    *partial = Some((summary.clone(), block));
+}
+
+/// on a panic, emit the last `record_partial` summary (marked partial in its `mode`) and the
+/// last nonce and block before the usual panic message, so a crashed experiment still leaves
+/// results behind
+pub fn install_panic_hook(format: OutputFormat, csv_path: String) {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        let partial = PARTIAL
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .take();
+        if let Some((mut summary, block)) = partial {
+            eprintln!(
+                "panicked mid-run at block {block}, nonce {}; partial results:",
+                summary.end_nonce
+            );
+            summary.mode = format!("{} (partial)", summary.mode);
+            if let Err(err) = summary.emit(format, &csv_path) {
+                eprintln!("could not write the partial summary: {err}");
+            }
+        }
+        default_hook(info);
+    }));
+}
+
+/// aggregate outcome of a run, for both the mempool and bundle modes
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Summary {
+    pub mode: String,
+    /// `--label`, to tell runs apart
+    pub label: String,
+    pub blocks: u64,
+    pub attempted: usize,
+    pub landed: usize,
+    pub success_rate: f64,
+    pub elapsed_secs: f64,
+    pub fill_pct: f64,
+    pub chunk_size: usize,
+    pub tip_wei: u64,
+    pub end_nonce: u64,
+    /// mempool transactions seen pending that were later dropped
+    pub evicted: usize,
+    /// with `--reorg-confirmations`, landed bundles (counted in `landed`) that hadn't reached
+    /// their confirmations yet when the run ended
+    pub tentative: usize,
+    /// gas paid by the landed transactions, from their receipts
+    pub spent_eth: f64,
+    /// value sent by the landed transactions, with `--value-range`
+    pub value_sent_eth: f64,
+    /// calldata in landed bundles
+    pub landed_calldata_bytes: usize,
+    /// landed calldata over the whole run's wall-clock time
+    pub sustained_bytes_per_sec: f64,
+    /// best single block, as its landed calldata over the time since the previous block.
+    /// only measured with `--measure-throughput`.
+    pub peak_bytes_per_sec: f64,
+    /// lowest tip `--discover-min-tip` found to land consistently, if it got there
+    pub discovered_min_tip_wei: Option<u64>,
+    /// with `--size-distribution`, the share of landed transactions from each bucket.  with
+    /// `--replay-distribution`, how closely the landed transactions follow the capture
+    pub size_distribution: String,
+    /// with `--analyze-blocks`, all calldata and gas in the blocks our bundles landed in,
+    /// and how much of it was ours
+    pub block_calldata_bytes: usize,
+    pub block_gas_used: u64,
+    pub calldata_share_pct: f64,
+    pub gas_share_pct: f64,
+    /// our part of `block_calldata_bytes` and `block_gas_used`
+    #[serde(skip)]
+    analyzed: (usize, u64),
+}
+
+impl Summary {
+    pub fn new(mode: &str, fill_pct: f64, chunk_size: usize, tip_wei: u64) -> Self {
+        Self {
+            mode: mode.to_string(),
+            fill_pct,
+            chunk_size,
+            tip_wei,
+            ..Default::default()
+        }
+    }
+
+    /// `--analyze-blocks`: count a landed bundle's `calldata_bytes` and `gas_used` against
+    /// the totals of the block it landed in
+    pub fn add_block_share(
+        &mut self,
+        calldata_bytes: usize,
+        gas_used: u64,
+        block_calldata_bytes: usize,
+        block_gas_used: u64,
+    ) {
+        self.analyzed.0 += calldata_bytes;
+        self.analyzed.1 += gas_used;
+        self.block_calldata_bytes += block_calldata_bytes;
+        self.block_gas_used += block_gas_used;
+        self.calldata_share_pct =
+            share_pct(self.analyzed.0 as u64, self.block_calldata_bytes as u64);
+        self.gas_share_pct = share_pct(self.analyzed.1, self.block_gas_used);
+    }
+
+    /// fill in the counters once the run is over
+    pub fn finish(&mut self, landed: usize, attempted: usize, elapsed: Duration) {
+        self.landed = landed;
+        self.attempted = attempted;
+        self.success_rate = crate::progress::success_rate(landed, attempted);
+        self.elapsed_secs = elapsed.as_secs_f64();
+        if self.elapsed_secs > 0.0 {
+            self.sustained_bytes_per_sec = self.landed_calldata_bytes as f64 / self.elapsed_secs;
+        }
+    }
+
+    pub fn emit(&self, format: OutputFormat, csv_path: &str) -> eyre::Result<()> {
+        match format {
+            OutputFormat::Table => eprint!("{}", self.to_table()?),
+            OutputFormat::Json => println!("{}", serde_json::to_string(self)?),
+            OutputFormat::Csv => self.append_csv(csv_path)?,
+        }
+        Ok(())
+    }
+
+    /// flattened (field, value) pairs, shared by the table and csv outputs
+    fn fields(&self) -> eyre::Result<Vec<(String, String)>> {
+        let Value::Object(map) = serde_json::to_value(self)? else {
+            eyre::bail!("summary did not serialize to an object");
+        };
+        Ok(map
+            .into_iter()
+            .map(|(key, value)| {
+                let value = match value {
+                    Value::String(s) => s,
+                    other => other.to_string(),
+                };
+                (key, value)
+            })
+            .collect())
+    }
+
+    pub fn to_table(&self) -> eyre::Result<String> {
+        let fields = self.fields()?;
+        let width = fields.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+        let mut table = String::new();
+        for (key, value) in fields {
+            table.push_str(&format!("{key:<width$}  {value}\n"));
+        }
+        Ok(table)
+    }
+
+    /// append one row, writing the header first if the file is new
+    pub fn append_csv(&self, path: &str) -> eyre::Result<()> {
+        let fields = self.fields()?;
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        if file.metadata()?.len() == 0 {
+            let header: Vec<String> = fields.iter().map(|(key, _)| csv_field(key)).collect();
+            writeln!(file, "{}", header.join(","))?;
+        }
+        let row: Vec<String> = fields.iter().map(|(_, value)| csv_field(value)).collect();
+        writeln!(file, "{}", row.join(","))?;
+        Ok(())
+    }
+}
+
+/// `ours` as a percentage of `total`, 0 if there's no total
+fn share_pct(ours: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        ours as f64 * 100.0 / total as f64
+    }
+}
+
+/// quote a csv field if it would otherwise break the row
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
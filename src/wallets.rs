@@ -0,0 +1,73 @@
+use ethers::prelude::*;
+use ethers::signers::coins_bip39::English;
+use eyre::Result;
+use std::ops::Range;
+
+/// `--wallet-index`: one index `N`, or a range `A..B` (not including `B`)
+pub fn index_range(s: &str) -> Result<Range<u32>, String> {
+    let parse = |index: &str| index.parse::<u32>().map_err(|e| format!("{e}: {index}"));
+    let range = match s.split_once("..") {
+        Some((start, end)) => parse(start)?..parse(end)?,
+        None => {
+            let index = parse(s)?;
+            index..index.saturating_add(1)
+        }
+    };
+    if range.is_empty() {
+        return Err(format!("wallet index range is empty: {s}"));
+    }
+    Ok(range)
+}
+
+/// the wallets at `m/44'/60'/0'/0/<index>` of `mnemonic` for every index in `indices`,
+/// logging each one's address
+pub fn derive(mnemonic: &str, indices: Range<u32>) -> Result<Vec<LocalWallet>> {
+    let mut wallets = Vec::new();
+    for index in indices {
+        let wallet = MnemonicBuilder::<English>::default()
+            .phrase(mnemonic)
+            .index(index)?
+            .build()
+            .map_err(|err| eyre::eyre!("invalid --mnemonic: {err}"))?;
+        tracing::info!(
+            "wallet {index} derived from --mnemonic: {:?}",
+            wallet.address()
+        );
+        wallets.push(wallet);
+    }
+    Ok(wallets)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the well known development mnemonic, never funded on a real network
+    const MNEMONIC: &str = "test test test test test test test test test test test junk";
+
+    #[test]
+    fn derives_the_standard_path() {
+        let wallets = derive(MNEMONIC, index_range("0..2").unwrap()).unwrap();
+        let addresses: Vec<Address> = wallets.iter().map(|wallet| wallet.address()).collect();
+        assert_eq!(
+            addresses,
+            vec![
+                "0xf39Fd6e51aad88F6F4ce6aB8827279cffFb92266"
+                    .parse()
+                    .unwrap(),
+                "0x70997970C51812dc3A010C7d01b50e0d17dc79C8"
+                    .parse()
+                    .unwrap(),
+            ]
+        );
+        assert!(derive("not a mnemonic", 0..1).is_err());
+    }
+
+    #[test]
+    fn parses_one_index_or_a_range() {
+        assert_eq!(index_range("7").unwrap(), 7..8);
+        assert_eq!(index_range("0..64").unwrap(), 0..64);
+        assert!(index_range("4..4").is_err());
+        assert!(index_range("x").is_err());
+    }
+}
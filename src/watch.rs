@@ -0,0 +1,85 @@
+use ethers::prelude::*;
+use ethers_flashbots::{PendingBundle, PendingBundleError};
+use eyre::Result;
+use std::sync::Arc;
+
+/// how `run` found a submitted bundle to have ended up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// included in this block, and still there after the confirmations
+    Landed(U64),
+    /// included in this block, but a reorg dropped it before the confirmations
+    Reorged(U64),
+    /// not included in any block of the range
+    Expired,
+}
+
+/// follow the bundle made of `transactions` (first transaction first) through blocks
+/// `from..=to`, each checked the way a pending bundle is once that block exists.  once it
+/// lands, keep watching for `confirmations` blocks so a reorg shows up too.  errors unless it
+/// landed for good.
+pub async fn run(
+    provider: Arc<Provider<Http>>,
+    bundle_name: Option<String>,
+    transactions: Vec<TxHash>,
+    from: U64,
+    to: U64,
+    confirmations: u64,
+) -> Result<()> {
+    let name = match bundle_name {
+        Some(name) => format!("bundle {name}"),
+        None => format!("bundle starting with {:?}", transactions[0]),
+    };
+    tracing::info!(
+        "watching {name} ({} transactions) in blocks {from}..={to}",
+        transactions.len()
+    );
+    let outcome = outcome(&provider, &transactions, from, to, confirmations).await?;
+    match outcome {
+        Outcome::Landed(block) => {
+            println!("{name} landed in block {block}, {confirmations} confirmations");
+            Ok(())
+        }
+        Outcome::Reorged(block) => {
+            eyre::bail!("{name} landed in block {block}, but was reorged out")
+        }
+        Outcome::Expired => eyre::bail!("{name} expired, not included in blocks {from}..={to}"),
+    }
+}
+
+async fn outcome(
+    provider: &Provider<Http>,
+    transactions: &[TxHash],
+    from: U64,
+    to: U64,
+    confirmations: u64,
+) -> Result<Outcome> {
+    let mut block = from;
+    while block <= to {
+        let pending =
+            PendingBundle::new(H256::zero(), block, transactions.to_vec(), provider).await;
+        match pending {
+            Ok(_) => break,
+            Err(PendingBundleError::BundleNotIncluded) => {
+                tracing::debug!("not in block {block}");
+                block += U64::one();
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+    if block > to {
+        return Ok(Outcome::Expired);
+    }
+    tracing::info!("included in block {block}");
+
+    if !crate::wait_for_confirmations(provider, transactions[0], block, confirmations).await? {
+        return Ok(Outcome::Reorged(block));
+    }
+    for tx_hash in &transactions[1..] {
+        if !crate::still_canonical(provider, *tx_hash, block).await? {
+            tracing::warn!("{tx_hash:?} is not in block {block} with the rest of the bundle");
+            return Ok(Outcome::Reorged(block));
+        }
+    }
+    Ok(Outcome::Landed(block))
+}